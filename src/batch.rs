@@ -0,0 +1,159 @@
+use crate::{make_hash, ConcurrentHashMap, ShardSelector};
+use std::hash::{BuildHasher, Hash};
+
+/// A single operation for [`ConcurrentHashMap::apply_batch`].
+///
+/// The lifetime lets `Update` closures borrow from the caller.
+pub enum Op<'a, K, V> {
+    /// Insert the pair, overwriting any existing value.
+    Insert(K, V),
+    /// Remove the key.
+    Remove(K),
+    /// Run the closure on the value if the key is present.
+    Update(K, Box<dyn FnOnce(&mut V) + 'a>),
+    /// Replace the value if the key is present, without inserting it otherwise.
+    Replace(K, V),
+}
+
+/// The result of one [`Op`], returned by [`ConcurrentHashMap::apply_batch`].
+#[derive(Debug, PartialEq, Eq)]
+pub enum OpResult<V> {
+    /// The value previously stored at the key, if any.
+    Inserted(Option<V>),
+    /// The removed value, if the key was present.
+    Removed(Option<V>),
+    /// Whether the key was present and the closure ran.
+    Updated(bool),
+    /// The replaced value, if the key was present.
+    Replaced(Option<V>),
+}
+
+impl<K, V> Op<'_, K, V> {
+    fn key(&self) -> &K {
+        match self {
+            Op::Insert(k, _) | Op::Remove(k) | Op::Update(k, _) | Op::Replace(k, _) => k,
+        }
+    }
+}
+
+impl<K, V, S: BuildHasher, const N: usize, P> ConcurrentHashMap<K, V, S, N, P> {
+    /// Applies a list of operations, returning one result per operation in the same order.
+    ///
+    /// Operations are grouped by shard and each shard's lock is taken once. Operations on the
+    /// same key always run in the order given. The batch as a whole is not atomic: readers may
+    /// see some shards updated before others.
+    ///
+    /// **Locks** - Acquires a write lock on each shard touched by an operation, one at a time.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use sharded::{ConcurrentHashMap, Op, OpResult};
+    ///
+    /// let map = ConcurrentHashMap::new();
+    /// let bonus = 10;
+    /// let results = map.apply_batch(vec![
+    ///     Op::Insert("a", 1),
+    ///     Op::Update("a", Box::new(|v: &mut i32| *v += bonus)),
+    ///     Op::Replace("b", 2),
+    ///     Op::Remove("b"),
+    /// ]);
+    ///
+    /// assert_eq!(
+    ///     results,
+    ///     vec![
+    ///         OpResult::Inserted(None),
+    ///         OpResult::Updated(true),
+    ///         OpResult::Replaced(None),
+    ///         OpResult::Removed(None),
+    ///     ]
+    /// );
+    /// assert_eq!(*map.get(&"a").unwrap(), 11);
+    /// ```
+    pub fn apply_batch(&self, ops: Vec<Op<'_, K, V>>) -> Vec<OpResult<V>>
+    where
+        K: Hash + Eq,
+        P: ShardSelector<K>,
+    {
+        let mut batches: Vec<Vec<_>> = (0..N).map(|_| Vec::new()).collect();
+        for (position, op) in ops.into_iter().enumerate() {
+            let hash = make_hash::<K, _>(&self.hash_builder, op.key());
+            batches[self.shard_for(hash, op.key())].push((position, hash, op));
+        }
+
+        let mut results = Vec::new();
+        for (i, batch) in batches.into_iter().enumerate() {
+            if batch.is_empty() {
+                continue;
+            }
+
+            let mut shard = self.shards[i].write();
+            for (position, hash, op) in batch {
+                let result = match op {
+                    Op::Insert(k, v) => {
+                        OpResult::Inserted(shard.insert(hash, k, v, &self.hash_builder))
+                    }
+                    Op::Remove(k) => OpResult::Removed(shard.remove(hash, &k)),
+                    Op::Update(k, f) => match shard.get_mut(hash, &k) {
+                        Some(v) => {
+                            f(v);
+                            OpResult::Updated(true)
+                        }
+                        None => OpResult::Updated(false),
+                    },
+                    Op::Replace(k, v) => OpResult::Replaced(
+                        shard
+                            .get_mut(hash, &k)
+                            .map(|current| std::mem::replace(current, v)),
+                    ),
+                };
+                results.push((position, result));
+            }
+            self.notifiers[i].notify();
+        }
+
+        results.sort_unstable_by_key(|(position, _)| *position);
+        results.into_iter().map(|(_, result)| result).collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::hash_map::RandomState;
+
+    #[test]
+    fn test_apply_batch_replays_in_order() {
+        let map: ConcurrentHashMap<u32, u32, RandomState, 4> = ConcurrentHashMap::default();
+
+        let mut log = Vec::new();
+        for i in 0..50 {
+            log.push(Op::Insert(i, i));
+            log.push(Op::Update(i, Box::new(|v: &mut u32| *v *= 2)));
+        }
+        log.push(Op::Remove(10));
+        log.push(Op::Update(10, Box::new(|_: &mut u32| panic!("removed"))));
+        log.push(Op::Insert(10, 1));
+        log.push(Op::Replace(10, 3));
+        log.push(Op::Replace(99, 3));
+
+        let results = map.apply_batch(log);
+
+        assert_eq!(results.len(), 105);
+        assert_eq!(results[0], OpResult::Inserted(None));
+        assert_eq!(results[1], OpResult::Updated(true));
+        assert_eq!(results[100], OpResult::Removed(Some(20)));
+        assert_eq!(results[101], OpResult::Updated(false));
+        assert_eq!(results[102], OpResult::Inserted(None));
+        assert_eq!(results[103], OpResult::Replaced(Some(1)));
+        assert_eq!(results[104], OpResult::Replaced(None));
+        assert_eq!(*map.get(&49).unwrap(), 98);
+        assert_eq!(*map.get(&10).unwrap(), 3);
+        assert!(!map.contains_key(&99));
+
+        // updates can borrow from the caller
+        let mut seen = Vec::new();
+        map.apply_batch(vec![Op::Update(10, Box::new(|v: &mut u32| seen.push(*v)))]);
+        assert_eq!(seen, [3]);
+    }
+}
@@ -0,0 +1,159 @@
+use crate::{ConcurrentHashMap, HashSelector, ReadPolicy, DEFAULT_SHARD_COUNT};
+use std::collections::hash_map::RandomState;
+use std::hash::BuildHasher;
+use std::marker::PhantomData;
+
+/// A builder for configuring a [`ConcurrentHashMap`].
+///
+/// This `struct` is created by [`ConcurrentHashMap::builder`]. The shard count is part of the
+/// map's type, so [`shards`](Self::shards) takes it as a const generic.
+pub struct ConcurrentHashMapBuilder<
+    K,
+    V,
+    S = RandomState,
+    const N: usize = DEFAULT_SHARD_COUNT,
+    P = HashSelector,
+> {
+    capacity: usize,
+    hash_builder: S,
+    selector: P,
+    read_policy: ReadPolicy,
+    _marker: PhantomData<fn() -> (K, V)>,
+}
+
+impl<K, V> ConcurrentHashMap<K, V, RandomState, DEFAULT_SHARD_COUNT> {
+    /// Creates a builder with the default hasher, shard count and no pre-allocated capacity.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use sharded::ConcurrentHashMap;
+    /// use std::collections::hash_map::RandomState;
+    ///
+    /// let map = ConcurrentHashMap::builder()
+    ///     .shards::<64>()
+    ///     .capacity(1_000)
+    ///     .hasher(RandomState::new())
+    ///     .build();
+    /// map.insert("a", 1);
+    ///
+    /// assert_eq!(map.config().shard_count, 64);
+    /// assert!(map.capacity() >= 1_000);
+    /// ```
+    pub fn builder() -> ConcurrentHashMapBuilder<K, V> {
+        ConcurrentHashMapBuilder {
+            capacity: 0,
+            hash_builder: RandomState::new(),
+            selector: HashSelector,
+            read_policy: ReadPolicy::Fair,
+            _marker: PhantomData,
+        }
+    }
+}
+
+impl<K, V, S, const N: usize, P> ConcurrentHashMapBuilder<K, V, S, N, P> {
+    /// Sets the number of shards.
+    ///
+    /// `M` must be greater than zero, which is checked at compile time:
+    ///
+    /// ```compile_fail
+    /// use sharded::ConcurrentHashMap;
+    ///
+    /// let map = ConcurrentHashMap::<u32, u32>::builder().shards::<0>().build();
+    /// ```
+    pub fn shards<const M: usize>(self) -> ConcurrentHashMapBuilder<K, V, S, M, P> {
+        ConcurrentHashMapBuilder {
+            capacity: self.capacity,
+            hash_builder: self.hash_builder,
+            selector: self.selector,
+            read_policy: self.read_policy,
+            _marker: PhantomData,
+        }
+    }
+
+    /// Sets the number of elements the map can hold without reallocating, split evenly over
+    /// the shards.
+    pub fn capacity(self, capacity: usize) -> Self {
+        ConcurrentHashMapBuilder { capacity, ..self }
+    }
+
+    /// Sets the hash builder.
+    pub fn hasher<S2>(self, hash_builder: S2) -> ConcurrentHashMapBuilder<K, V, S2, N, P> {
+        ConcurrentHashMapBuilder {
+            capacity: self.capacity,
+            hash_builder,
+            selector: self.selector,
+            read_policy: self.read_policy,
+            _marker: PhantomData,
+        }
+    }
+
+    /// Sets the [`ShardSelector`](crate::ShardSelector) routing keys to shards.
+    pub fn selector<P2>(self, selector: P2) -> ConcurrentHashMapBuilder<K, V, S, N, P2> {
+        ConcurrentHashMapBuilder {
+            capacity: self.capacity,
+            hash_builder: self.hash_builder,
+            selector,
+            read_policy: self.read_policy,
+            _marker: PhantomData,
+        }
+    }
+
+    /// Sets whether readers queue behind waiting writers, [`ReadPolicy::Fair`] by default.
+    ///
+    /// The `bustle` results in the README's performance comparison, including the read heavy
+    /// mix, were measured with the fair policy, and it is the right choice whenever writes are
+    /// frequent enough that stalling them matters. Consider [`ReadPolicy::PreferReaders`] when
+    /// writes are rare, e.g. a configuration cache, and readers must never wait on a queued
+    /// writer, then benchmark both on your workload.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use sharded::{ConcurrentHashMap, ReadPolicy};
+    ///
+    /// let map = ConcurrentHashMap::builder()
+    ///     .read_policy(ReadPolicy::PreferReaders)
+    ///     .build();
+    /// map.insert("a", 1);
+    /// assert_eq!(map.read_policy(), ReadPolicy::PreferReaders);
+    /// ```
+    pub fn read_policy(self, read_policy: ReadPolicy) -> Self {
+        ConcurrentHashMapBuilder {
+            read_policy,
+            ..self
+        }
+    }
+
+    /// Builds the map.
+    pub fn build(self) -> ConcurrentHashMap<K, V, S, N, P>
+    where
+        S: BuildHasher,
+    {
+        let mut map = ConcurrentHashMap::with_capacity_hasher_and_selector(
+            self.capacity,
+            self.hash_builder,
+            self.selector,
+        );
+        map.set_read_policy(self.read_policy);
+        map
+    }
+
+    /// Builds the map, allocating its shards on up to `threads` scoped threads. See
+    /// [`ConcurrentHashMap::with_capacity_parallel`].
+    pub fn build_parallel(self, threads: usize) -> ConcurrentHashMap<K, V, S, N, P>
+    where
+        K: Send,
+        V: Send,
+        S: BuildHasher,
+    {
+        let mut map = ConcurrentHashMap::with_capacity_parallel(
+            self.capacity,
+            self.hash_builder,
+            self.selector,
+            threads,
+        );
+        map.set_read_policy(self.read_policy);
+        map
+    }
+}
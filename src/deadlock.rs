@@ -0,0 +1,225 @@
+//! Deadlock detection for shard locks, enabled by the `deadlock_detection` feature.
+//!
+//! This wraps parking_lot's experimental detector, which tracks every parking_lot lock in the
+//! process, not just this crate's. It cannot be combined with the `send_guard` feature.
+//!
+//! # Examples
+//!
+//! ```no_run
+//! use std::time::Duration;
+//!
+//! std::thread::spawn(|| loop {
+//!     std::thread::sleep(Duration::from_secs(10));
+//!     for cycle in sharded::deadlock::check() {
+//!         eprintln!("deadlock between {} threads", cycle.len());
+//!         for thread in cycle {
+//!             eprintln!("{:?} in {:?}", thread.thread_id(), thread.operations());
+//!             if let Some(blocked) = thread.blocked_on() {
+//!                 eprintln!("  waiting for shard {} of the map at {:#x}", blocked.shard, blocked.map);
+//!             }
+//!         }
+//!     }
+//! });
+//! ```
+
+use std::fmt::Write;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex, PoisonError};
+use std::thread::ThreadId;
+
+/// A thread that is part of a deadlock cycle.
+#[derive(Clone, Debug)]
+pub struct DeadlockedThread {
+    thread_id: ThreadId,
+    operations: Vec<String>,
+    backtrace: String,
+    blocked_on: Option<BlockedOn>,
+}
+
+/// The shard lock a deadlocked thread is waiting for, see [`DeadlockedThread::blocked_on`].
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[non_exhaustive]
+pub struct BlockedOn {
+    /// The address of the map, e.g. `Arc::as_ptr(&map) as usize`
+    pub map: usize,
+    /// The index of the shard within the map
+    pub shard: usize,
+}
+
+impl DeadlockedThread {
+    /// Returns the thread's id, see [`std::thread::Thread::id`].
+    pub fn thread_id(&self) -> ThreadId {
+        self.thread_id
+    }
+
+    /// Returns the calls into this crate on the thread's stack, innermost first, e.g. the
+    /// `insert` waiting for a shard. Guards the thread holds from earlier calls don't appear,
+    /// look for them in the [`backtrace`](Self::backtrace) of the caller.
+    pub fn operations(&self) -> &[String] {
+        &self.operations
+    }
+
+    /// Returns the thread's full backtrace, captured when it blocked.
+    pub fn backtrace(&self) -> &str {
+        &self.backtrace
+    }
+
+    /// Returns the map and shard whose lock the thread is waiting for, or `None` if it is
+    /// blocked on a lock outside this crate.
+    pub fn blocked_on(&self) -> Option<BlockedOn> {
+        self.blocked_on
+    }
+}
+
+/// The shard lock one thread is waiting for, a `map` of zero meaning none.
+#[derive(Default)]
+struct Waiting {
+    map: AtomicUsize,
+    shard: AtomicUsize,
+}
+
+// every thread that has locked a shard, the detector reads them from its own thread
+static WAITING: Mutex<Vec<(ThreadId, Arc<Waiting>)>> = Mutex::new(Vec::new());
+
+/// This thread's entry in `WAITING`, removed when the thread exits.
+struct Registration(ThreadId, Arc<Waiting>);
+
+impl Registration {
+    fn new() -> Self {
+        let (id, waiting) = (std::thread::current().id(), Arc::<Waiting>::default());
+        registry().push((id, waiting.clone()));
+        Registration(id, waiting)
+    }
+}
+
+impl Drop for Registration {
+    fn drop(&mut self) {
+        registry().retain(|(id, _)| *id != self.0);
+    }
+}
+
+thread_local! {
+    static REGISTRATION: Registration = Registration::new();
+}
+
+fn registry() -> std::sync::MutexGuard<'static, Vec<(ThreadId, Arc<Waiting>)>> {
+    WAITING.lock().unwrap_or_else(PoisonError::into_inner)
+}
+
+/// Runs a blocking `acquire` of shard `shard` of the map at `map`, recording it as the lock
+/// this thread waits for until it returns.
+pub(crate) fn blocking<R>(map: usize, shard: usize, acquire: impl FnOnce() -> R) -> R {
+    // during thread teardown the registration is gone, the acquire just isn't recorded
+    let _ = REGISTRATION.try_with(|registration| {
+        registration.1.shard.store(shard, Ordering::Relaxed);
+        registration.1.map.store(map, Ordering::Release);
+    });
+    let result = acquire();
+    let _ = REGISTRATION.try_with(|registration| registration.1.map.store(0, Ordering::Release));
+    result
+}
+
+fn blocked_on(thread_id: ThreadId) -> Option<BlockedOn> {
+    let registry = registry();
+    let (_, waiting) = registry.iter().find(|(id, _)| *id == thread_id)?;
+    match waiting.map.load(Ordering::Acquire) {
+        0 => None,
+        map => Some(BlockedOn {
+            map,
+            shard: waiting.shard.load(Ordering::Relaxed),
+        }),
+    }
+}
+
+/// Returns the deadlock cycles found since the last call, each as the threads involved.
+///
+/// Each cycle is only reported once. Call it periodically from a background thread.
+pub fn check() -> Vec<Vec<DeadlockedThread>> {
+    parking_lot::deadlock::check_deadlock()
+        .into_iter()
+        .map(|cycle| {
+            cycle
+                .into_iter()
+                .map(|thread| {
+                    let mut operations = Vec::new();
+                    for frame in thread.backtrace().frames() {
+                        for symbol in frame.symbols() {
+                            let Some(name) = symbol.name() else { continue };
+                            let mut buf = String::new();
+                            // `{:#}` drops the symbol hash
+                            let _ = write!(buf, "{:#}", name);
+                            if buf.starts_with("sharded::") && !buf.starts_with("sharded::deadlock")
+                            {
+                                operations.push(buf);
+                            }
+                        }
+                    }
+
+                    DeadlockedThread {
+                        thread_id: thread.thread_id(),
+                        operations,
+                        backtrace: format!("{:?}", thread.backtrace()),
+                        blocked_on: blocked_on(thread.thread_id()),
+                    }
+                })
+                .collect()
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{make_hash, ConcurrentHashMap};
+    use std::collections::hash_map::RandomState;
+    use std::sync::{Arc, Barrier};
+    use std::time::Duration;
+
+    #[test]
+    fn test_reports_cross_map_cycle() {
+        let a: Arc<ConcurrentHashMap<u32, u32, RandomState, 4>> = Arc::default();
+        let b: Arc<ConcurrentHashMap<u32, u32, RandomState, 4>> = Arc::default();
+        a.insert(1, 1);
+        b.insert(1, 1);
+        let barrier = Arc::new(Barrier::new(2));
+
+        let mut waits_for = Vec::new();
+        for (first, second) in [(a.clone(), b.clone()), (b, a)] {
+            let barrier = barrier.clone();
+            let shard = second.shard_for(make_hash::<u32, _>(second.hasher(), &1), &1);
+            let expected = (Arc::as_ptr(&second) as usize, shard);
+            // leaked on purpose, the threads never finish
+            let thread = std::thread::spawn(move || {
+                let _held = first.get_mut(&1);
+                barrier.wait();
+                second.insert(1, 2);
+            });
+            waits_for.push((thread.thread().id(), expected));
+        }
+
+        let cycle = loop {
+            std::thread::sleep(Duration::from_millis(10));
+            if let Some(cycle) = super::check().pop() {
+                break cycle;
+            }
+        };
+        assert_eq!(cycle.len(), 2);
+        assert!(cycle.iter().all(|thread| !thread.backtrace().is_empty()));
+        // release builds may inline `insert` away, the shard location below still holds
+        #[cfg(debug_assertions)]
+        assert!(cycle.iter().all(|thread| thread
+            .operations()
+            .iter()
+            .any(|op| op.ends_with("::insert"))));
+        assert!(cycle
+            .iter()
+            .all(|thread| thread.thread_id() != std::thread::current().id()));
+        for thread in &cycle {
+            let (_, expected) = waits_for
+                .iter()
+                .find(|(id, _)| *id == thread.thread_id())
+                .unwrap();
+            let blocked = thread.blocked_on().unwrap();
+            assert_eq!((blocked.map, blocked.shard), *expected);
+        }
+    }
+}
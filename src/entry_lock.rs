@@ -0,0 +1,227 @@
+use crate::{ConcurrentHashMap, Equivalent, RawEntryMut, DEFAULT_SHARD_COUNT};
+use parking_lot::{ArcMutexGuard, Mutex, RawMutex};
+use std::collections::hash_map::RandomState;
+use std::hash::{BuildHasher, Hash};
+use std::sync::Arc;
+
+/// An exclusive guard on one entry of an [`EntryLockMap`]. It holds no shard lock.
+pub type EntryGuard<V> = ArcMutexGuard<RawMutex, V>;
+
+/// A sharded map with a lock per entry, for workloads with a few very hot keys.
+///
+/// The shard lock is only held long enough to find an entry, updates then serialize on the
+/// entry's own lock. Other keys in the same shard proceed while a hot key is being updated,
+/// at the cost of an allocation and a lock per entry.
+///
+/// # Examples
+///
+/// ```
+/// use sharded::EntryLockMap;
+///
+/// let counters = EntryLockMap::new();
+/// *counters.lock_or_insert_with("hot", || 0) += 1;
+///
+/// let mut hot = counters.lock_entry("hot").unwrap();
+/// // other keys, even in the same shard, aren't blocked by `hot`
+/// *counters.lock_or_insert_with("cold", || 0) += 1;
+/// *hot += 1;
+/// assert_eq!(*hot, 2);
+/// ```
+pub struct EntryLockMap<K, V, S = RandomState, const N: usize = DEFAULT_SHARD_COUNT> {
+    entries: ConcurrentHashMap<K, Arc<Mutex<V>>, S, N>,
+}
+
+impl<K, V> EntryLockMap<K, V, RandomState, DEFAULT_SHARD_COUNT> {
+    /// Creates an empty `EntryLockMap`.
+    #[must_use]
+    pub fn new() -> EntryLockMap<K, V, RandomState> {
+        Default::default()
+    }
+}
+
+impl<K, V, S, const N: usize> Default for EntryLockMap<K, V, S, N>
+where
+    S: Default + BuildHasher,
+{
+    fn default() -> Self {
+        EntryLockMap {
+            entries: ConcurrentHashMap::default(),
+        }
+    }
+}
+
+impl<K, V, S: BuildHasher, const N: usize> EntryLockMap<K, V, S, N> {
+    /// Locks the entry for `key`, if present.
+    ///
+    /// **Locks** - Acquires a read lock on one of `N` shards briefly, then the entry's lock for
+    /// the returned guard.
+    pub fn lock_entry<Q>(&self, key: &Q) -> Option<EntryGuard<V>>
+    where
+        Q: Hash + Equivalent<K> + ?Sized,
+    {
+        loop {
+            let entry = self.entries.get_cloned(key)?;
+            if let Some(guard) = self.lock_current(key, entry) {
+                return Some(guard);
+            }
+        }
+    }
+
+    /// Locks the entry for `key`, inserting the result of `f` first if it is absent.
+    ///
+    /// **Locks** - Acquires a read lock on one of `N` shards briefly, plus a write lock if the
+    /// key is absent, then the entry's lock for the returned guard.
+    pub fn lock_or_insert_with<F>(&self, key: K, f: F) -> EntryGuard<V>
+    where
+        K: Hash + Eq,
+        F: FnOnce() -> V,
+    {
+        loop {
+            let entry = match self.entries.get_cloned(&key) {
+                Some(entry) => entry,
+                None => match self.entries.raw_entry_mut().from_key(&key) {
+                    RawEntryMut::Occupied(entry) => Arc::clone(entry.get()),
+                    RawEntryMut::Vacant(entry) => {
+                        // locked before it is published, so no one can remove it in between
+                        let fresh = Arc::new(Mutex::new(f()));
+                        let guard = fresh.lock_arc();
+                        entry.insert(key, fresh);
+                        return guard;
+                    }
+                },
+            };
+            if let Some(guard) = self.lock_current(&key, entry) {
+                return guard;
+            }
+        }
+    }
+
+    /// Sets the value for `key`, returning the previous value if there was one. An existing
+    /// entry is updated under its lock, so this waits for current holders of the entry.
+    ///
+    /// **Locks** - Acquires a read lock on one of `N` shards briefly, plus a write lock if the
+    /// key is absent, or the entry's lock if it is present.
+    pub fn insert(&self, key: K, value: V) -> Option<V>
+    where
+        K: Hash + Eq,
+    {
+        loop {
+            let entry = match self.entries.get_cloned(&key) {
+                Some(entry) => entry,
+                None => match self.entries.raw_entry_mut().from_key(&key) {
+                    RawEntryMut::Occupied(entry) => Arc::clone(entry.get()),
+                    RawEntryMut::Vacant(entry) => {
+                        entry.insert(key, Arc::new(Mutex::new(value)));
+                        return None;
+                    }
+                },
+            };
+            if let Some(mut guard) = self.lock_current(&key, entry) {
+                return Some(std::mem::replace(&mut *guard, value));
+            }
+        }
+    }
+
+    /// Locks `entry` and checks that it is still the one stored at `key`, since a `remove`
+    /// may have detached it while this thread waited for its lock.
+    fn lock_current<Q>(&self, key: &Q, entry: Arc<Mutex<V>>) -> Option<EntryGuard<V>>
+    where
+        Q: Hash + Equivalent<K> + ?Sized,
+    {
+        let guard = entry.lock_arc();
+        let current = self
+            .entries
+            .with(key, |current| Arc::ptr_eq(current, &entry));
+        current.unwrap_or(false).then_some(guard)
+    }
+
+    /// Removes `key`, returning `true` if it was present. Holders of the entry's guard keep
+    /// the value until they drop it.
+    ///
+    /// **Locks** - Acquires a write lock on one of `N` shards.
+    pub fn remove<Q>(&self, key: &Q) -> bool
+    where
+        Q: Hash + Equivalent<K> + ?Sized,
+    {
+        self.entries.remove(key).is_some()
+    }
+
+    /// Returns `true` if the map contains `key`.
+    ///
+    /// **Locks** - Acquires a read lock on one of `N` shards.
+    pub fn contains_key<Q>(&self, key: &Q) -> bool
+    where
+        Q: Hash + Equivalent<K> + ?Sized,
+    {
+        self.entries.contains_key(key)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Duration;
+
+    #[test]
+    fn test_hot_entry_does_not_block_its_shard() {
+        let map: Arc<EntryLockMap<u32, u32, RandomState, 1>> = Arc::default();
+        map.insert(1, 0);
+
+        let hot = map.lock_entry(&1).unwrap();
+        let other = {
+            let map = map.clone();
+            std::thread::spawn(move || {
+                // single shard, so this would block behind `hot` without entry locks
+                assert_eq!(map.insert(2, 5), None);
+                assert_eq!(*map.lock_entry(&2).unwrap(), 5);
+            })
+        };
+        other.join().unwrap();
+        drop(hot);
+
+        let workers: Vec<_> = (0..4)
+            .map(|_| {
+                let map = map.clone();
+                std::thread::spawn(move || {
+                    for _ in 0..1000 {
+                        *map.lock_or_insert_with(1, || 0) += 1;
+                    }
+                })
+            })
+            .collect();
+        for worker in workers {
+            worker.join().unwrap();
+        }
+
+        assert_eq!(map.insert(1, 0), Some(4000));
+        assert!(map.remove(&1));
+        assert!(!map.contains_key(&1));
+        assert!(map.lock_entry(&1).is_none());
+    }
+
+    #[test]
+    fn test_writes_survive_a_concurrent_remove() {
+        let map: Arc<EntryLockMap<u32, u32, RandomState, 1>> = Arc::default();
+        map.insert(1, 0);
+        map.insert(2, 0);
+
+        let held = (map.lock_entry(&1).unwrap(), map.lock_entry(&2).unwrap());
+        let writer = {
+            let map = map.clone();
+            std::thread::spawn(move || {
+                let previous = map.insert(1, 5);
+                *map.lock_or_insert_with(2, || 0) += 1;
+                previous
+            })
+        };
+        // let the writer find the old entries and wait on them, then detach them
+        std::thread::sleep(Duration::from_millis(50));
+        assert!(map.remove(&1));
+        assert!(map.remove(&2));
+        drop(held);
+
+        assert_eq!(writer.join().unwrap(), None);
+        assert_eq!(*map.lock_entry(&1).unwrap(), 5);
+        assert_eq!(*map.lock_entry(&2).unwrap(), 1);
+    }
+}
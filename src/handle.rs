@@ -0,0 +1,234 @@
+use crate::{
+    ConcurrentHashMap, Cursor, Equivalent, HashSelector, IterCloned, ReadGuard, ShardSelector,
+    WouldBlock, DEFAULT_SHARD_COUNT,
+};
+use std::collections::hash_map::RandomState;
+use std::hash::{BuildHasher, Hash};
+use std::ops::Deref;
+use std::sync::Arc;
+use std::time::Duration;
+
+/// A cloneable, read-only handle to a [`ConcurrentHashMap`].
+///
+/// This `struct` is created by [`ConcurrentHashMap::split`]. It only exposes lookups, so
+/// components holding one can never mutate the shared map.
+pub struct ReadHandle<K, V, S = RandomState, const N: usize = DEFAULT_SHARD_COUNT, P = HashSelector>
+{
+    map: Arc<ConcurrentHashMap<K, V, S, N, P>>,
+}
+
+/// The unique, mutating handle to a [`ConcurrentHashMap`].
+///
+/// This `struct` is created by [`ConcurrentHashMap::split`]. It dereferences to the map, so
+/// the full API is available, but it cannot be cloned.
+pub struct WriteHandle<
+    K,
+    V,
+    S = RandomState,
+    const N: usize = DEFAULT_SHARD_COUNT,
+    P = HashSelector,
+> {
+    map: Arc<ConcurrentHashMap<K, V, S, N, P>>,
+}
+
+impl<K, V, S, const N: usize, P> ConcurrentHashMap<K, V, S, N, P> {
+    /// Splits the map into a cloneable [`ReadHandle`] and a unique [`WriteHandle`] over the
+    /// same shards.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use sharded::ConcurrentHashMap;
+    ///
+    /// let (reader, writer) = ConcurrentHashMap::new().split();
+    /// writer.insert(1, "a");
+    ///
+    /// let reader = reader.clone();
+    /// assert_eq!(*reader.get(&1).unwrap(), "a");
+    /// ```
+    #[allow(clippy::type_complexity)]
+    pub fn split(self) -> (ReadHandle<K, V, S, N, P>, WriteHandle<K, V, S, N, P>) {
+        let map = Arc::new(self);
+        (ReadHandle { map: map.clone() }, WriteHandle { map })
+    }
+}
+
+impl<K, V, S, const N: usize, P> Clone for ReadHandle<K, V, S, N, P> {
+    fn clone(&self) -> Self {
+        ReadHandle {
+            map: self.map.clone(),
+        }
+    }
+}
+
+impl<K, V, S: BuildHasher, const N: usize, P> ReadHandle<K, V, S, N, P> {
+    /// See [`ConcurrentHashMap::get`].
+    #[inline]
+    pub fn get<Q>(&self, key: &Q) -> Option<ReadGuard<'_, V>>
+    where
+        Q: Hash + Equivalent<K> + ?Sized,
+        P: ShardSelector<Q>,
+    {
+        self.map.get(key)
+    }
+
+    /// See [`ConcurrentHashMap::get_cloned`].
+    #[inline]
+    pub fn get_cloned<Q>(&self, key: &Q) -> Option<V>
+    where
+        Q: Hash + Equivalent<K> + ?Sized,
+        P: ShardSelector<Q>,
+        V: Clone,
+    {
+        self.map.get_cloned(key)
+    }
+
+    /// See [`ConcurrentHashMap::with`].
+    #[inline]
+    pub fn with<Q, F, U>(&self, key: &Q, f: F) -> Option<U>
+    where
+        Q: Hash + Equivalent<K> + ?Sized,
+        P: ShardSelector<Q>,
+        F: FnOnce(&V) -> U,
+    {
+        self.map.with(key, f)
+    }
+
+    /// See [`ConcurrentHashMap::get_key_value`].
+    #[inline]
+    pub fn get_key_value<Q>(&self, key: &Q) -> Option<ReadGuard<'_, (K, V)>>
+    where
+        Q: Hash + Equivalent<K> + ?Sized,
+        P: ShardSelector<Q>,
+    {
+        self.map.get_key_value(key)
+    }
+
+    /// See [`ConcurrentHashMap::get_many`].
+    #[inline]
+    pub fn get_many<Q, const M: usize>(&self, keys: [&Q; M]) -> [Option<ReadGuard<'_, V>>; M]
+    where
+        Q: Hash + Equivalent<K> + ?Sized,
+        P: ShardSelector<Q>,
+    {
+        self.map.get_many(keys)
+    }
+
+    /// See [`ConcurrentHashMap::contains_key`].
+    #[inline]
+    pub fn contains_key<Q>(&self, key: &Q) -> bool
+    where
+        Q: Hash + Equivalent<K> + ?Sized,
+        P: ShardSelector<Q>,
+    {
+        self.map.contains_key(key)
+    }
+
+    /// See [`ConcurrentHashMap::try_get`].
+    #[inline]
+    pub fn try_get<Q>(&self, key: &Q) -> Result<Option<ReadGuard<'_, V>>, WouldBlock>
+    where
+        Q: Hash + Equivalent<K> + ?Sized,
+        P: ShardSelector<Q>,
+    {
+        self.map.try_get(key)
+    }
+
+    /// See [`ConcurrentHashMap::try_get_for`].
+    #[inline]
+    pub fn try_get_for<Q>(
+        &self,
+        key: &Q,
+        timeout: Duration,
+    ) -> Result<Option<ReadGuard<'_, V>>, WouldBlock>
+    where
+        Q: Hash + Equivalent<K> + ?Sized,
+        P: ShardSelector<Q>,
+    {
+        self.map.try_get_for(key, timeout)
+    }
+
+    /// See [`ConcurrentHashMap::for_each`].
+    #[inline]
+    pub fn for_each<F>(&self, f: F)
+    where
+        F: FnMut(&K, &V),
+    {
+        self.map.for_each(f)
+    }
+
+    /// See [`ConcurrentHashMap::fold`].
+    #[inline]
+    pub fn fold<B, F>(&self, init: B, f: F) -> B
+    where
+        F: FnMut(B, (&K, &V)) -> B,
+    {
+        self.map.fold(init, f)
+    }
+
+    /// See [`ConcurrentHashMap::iter_cloned`].
+    #[inline]
+    pub fn iter_cloned(&self) -> IterCloned<'_, K, V, S, N, P> {
+        self.map.iter_cloned()
+    }
+
+    /// See [`ConcurrentHashMap::split_iter`].
+    #[inline]
+    pub fn split_iter(&self, n: usize) -> Vec<IterCloned<'_, K, V, S, N, P>> {
+        self.map.split_iter(n)
+    }
+
+    /// See [`ConcurrentHashMap::scan`].
+    #[inline]
+    pub fn scan(&self, cursor: Cursor, limit: usize) -> (Vec<(K, V)>, Option<Cursor>)
+    where
+        K: Clone,
+        V: Clone,
+    {
+        self.map.scan(cursor, limit)
+    }
+}
+
+impl<K, V, S, const N: usize, P> WriteHandle<K, V, S, N, P> {
+    /// Creates another [`ReadHandle`] over the same map.
+    pub fn read_handle(&self) -> ReadHandle<K, V, S, N, P> {
+        ReadHandle {
+            map: self.map.clone(),
+        }
+    }
+}
+
+impl<K, V, S, const N: usize, P> Deref for WriteHandle<K, V, S, N, P> {
+    type Target = ConcurrentHashMap<K, V, S, N, P>;
+
+    fn deref(&self) -> &Self::Target {
+        &self.map
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::ConcurrentHashMap;
+
+    #[test]
+    fn test_split_handles_share_shards() {
+        let (reader, writer) = ConcurrentHashMap::new().split();
+        let other = writer.read_handle();
+
+        writer.insert("k", 1);
+        assert_eq!(*reader.get(&"k").unwrap(), 1);
+
+        *writer.get_mut(&"k").unwrap() += 1;
+        assert_eq!(*other.get(&"k").unwrap(), 2);
+
+        let reader = reader.clone();
+        std::thread::spawn(move || assert!(reader.contains_key(&"k")))
+            .join()
+            .unwrap();
+
+        // the read-only iteration APIs are forwarded too
+        assert_eq!(other.fold(0, |acc, (_, v)| acc + v), 2);
+        assert_eq!(other.iter_cloned().collect::<Vec<_>>(), [("k", 2)]);
+        assert!(other.try_get(&"k").unwrap().is_some());
+    }
+}
@@ -0,0 +1,226 @@
+use crate::{
+    downgrade_to_bucket, make_hash, ConcurrentHashMap, OccupiedError, ReadGuard, ShardSelector,
+    WriteGuard,
+};
+use std::hash::{BuildHasher, Hash};
+
+/// A key bundled with its hash under one map's hasher.
+///
+/// This `struct` is created by [`ConcurrentHashMap::hash_key`]. Passing it to the `_hashed`
+/// methods skips hashing, so a hot loop that checks, updates and maybe removes the same key
+/// hashes it exactly once. It must only be used with the map that created it, or lookups will
+/// miss.
+///
+/// Every method with a `_hashed` twin takes it: `get`, `get_mut`, `contains_key`, `insert`,
+/// `try_insert`, `get_or_try_insert_with`, `remove` and `remove_if`. For entry-style access,
+/// pass [`hash`](Self::hash) to
+/// [`raw_entry_mut().from_hash`](crate::RawEntryBuilderMut::from_hash), which never rehashes.
+/// Other methods hash the key themselves.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct HashedKey<K> {
+    hash: u64,
+    key: K,
+}
+
+impl<K> HashedKey<K> {
+    /// Returns the key.
+    pub fn key(&self) -> &K {
+        &self.key
+    }
+
+    /// Returns the precomputed hash.
+    pub fn hash(&self) -> u64 {
+        self.hash
+    }
+
+    /// Consumes the wrapper, returning the key.
+    pub fn into_key(self) -> K {
+        self.key
+    }
+}
+
+impl<K, V, S: BuildHasher, const N: usize, P> ConcurrentHashMap<K, V, S, N, P>
+where
+    K: Hash + Eq,
+    P: ShardSelector<K>,
+{
+    /// Hashes `key` with the map's hasher, for use with the `_hashed` methods.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use sharded::ConcurrentHashMap;
+    ///
+    /// let map = ConcurrentHashMap::new();
+    /// let key = map.hash_key("hits");
+    ///
+    /// map.insert_hashed(key, 0);
+    /// for _ in 0..3 {
+    ///     *map.get_mut_hashed(&key).unwrap() += 1;
+    /// }
+    /// assert_eq!(*map.get_hashed(&key).unwrap(), 3);
+    /// assert_eq!(map.remove_hashed(&key), Some(3));
+    /// ```
+    pub fn hash_key(&self, key: K) -> HashedKey<K> {
+        HashedKey {
+            hash: make_hash::<K, _>(&self.hash_builder, &key),
+            key,
+        }
+    }
+
+    /// Like [`get`](Self::get), without hashing the key.
+    ///
+    /// **Locks** - Acquires a read lock on one of `N` shards for the returned guard.
+    pub fn get_hashed(&self, key: &HashedKey<K>) -> Option<ReadGuard<'_, V>> {
+        let shard = self.shards[self.shard_for(key.hash, &key.key)].read();
+        shard.try_map(|shard| shard.get(key.hash, &key.key))
+    }
+
+    /// Like [`get_mut`](Self::get_mut), without hashing the key.
+    ///
+    /// **Locks** - Acquires a write lock on one of `N` shards for the returned guard.
+    pub fn get_mut_hashed(&self, key: &HashedKey<K>) -> Option<WriteGuard<'_, V>> {
+        let shard = self.shards[self.shard_for(key.hash, &key.key)].write();
+        shard
+            .try_map(|shard| shard.get_mut(key.hash, &key.key))
+            .ok()
+    }
+
+    /// Like [`contains_key`](Self::contains_key), without hashing the key.
+    ///
+    /// **Locks** - Acquires a read lock on one of `N` shards.
+    pub fn contains_key_hashed(&self, key: &HashedKey<K>) -> bool {
+        self.shards[self.shard_for(key.hash, &key.key)]
+            .read()
+            .get(key.hash, &key.key)
+            .is_some()
+    }
+
+    /// Like [`insert`](Self::insert), without hashing the key.
+    ///
+    /// **Locks** - Acquires a write lock on one of `N` shards.
+    pub fn insert_hashed(&self, key: HashedKey<K>, v: V) -> Option<V> {
+        let i = self.shard_for(key.hash, &key.key);
+        let previous = self.shards[i]
+            .write()
+            .insert(key.hash, key.key, v, &self.hash_builder);
+        self.notifiers[i].notify();
+        previous
+    }
+
+    /// Like [`try_insert`](Self::try_insert), without hashing the key.
+    ///
+    /// **Locks** - Acquires a write lock on one of `N` shards, which is held for as long as the
+    /// returned guard (or error) is alive.
+    pub fn try_insert_hashed(
+        &self,
+        key: HashedKey<K>,
+        v: V,
+    ) -> Result<WriteGuard<'_, V>, OccupiedError<'_, V>> {
+        let HashedKey { hash, key } = key;
+
+        let i = self.shard_for(hash, &key);
+
+        let shard = self.shards[i].write();
+
+        match shard.try_map(|shard| shard.get_mut(hash, &key)) {
+            Ok(existing) => Err(OccupiedError { existing, value: v }),
+            Err(shard) => {
+                let value =
+                    shard.map(|shard| shard.insert_unique(hash, key, v, &self.hash_builder));
+                self.notifiers[i].notify();
+                Ok(value)
+            }
+        }
+    }
+
+    /// Like [`get_or_try_insert_with`](Self::get_or_try_insert_with), without hashing the key.
+    ///
+    /// **Locks** - Acquires a read lock on one of `N` shards, or a write lock (downgraded to a
+    /// read lock) if the key is absent.
+    pub fn get_or_try_insert_with_hashed<F, E>(
+        &self,
+        key: HashedKey<K>,
+        f: F,
+    ) -> Result<ReadGuard<'_, V>, E>
+    where
+        F: FnOnce() -> Result<V, E>,
+    {
+        let HashedKey { hash, key } = key;
+
+        let i = self.shard_for(hash, &key);
+
+        let lock = &self.shards[i];
+
+        if let Some(value) = lock.read().try_map(|shard| shard.get(hash, &key)) {
+            return Ok(value);
+        }
+
+        let mut shard = lock.write();
+        let index = shard.try_get_or_insert_with(hash, key, f, &self.hash_builder)?;
+        self.notifiers[i].notify();
+
+        Ok(downgrade_to_bucket(shard, index))
+    }
+
+    /// Like [`remove`](Self::remove), without hashing the key.
+    ///
+    /// **Locks** - Acquires a write lock on one of `N` shards.
+    pub fn remove_hashed(&self, key: &HashedKey<K>) -> Option<V> {
+        self.shards[self.shard_for(key.hash, &key.key)]
+            .write()
+            .remove(key.hash, &key.key)
+    }
+
+    /// Like [`remove_if`](Self::remove_if), without hashing the key.
+    ///
+    /// **Locks** - Acquires a write lock on one of `N` shards.
+    pub fn remove_if_hashed<F>(&self, key: &HashedKey<K>, f: F) -> Option<(K, V)>
+    where
+        F: FnOnce(&K, &V) -> bool,
+    {
+        self.shards[self.shard_for(key.hash, &key.key)]
+            .write()
+            .remove_if(key.hash, &key.key, f)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::ConcurrentHashMap;
+    use std::collections::hash_map::RandomState;
+
+    #[test]
+    fn test_hashed_key_matches_plain_lookups() {
+        let map: ConcurrentHashMap<String, u32, RandomState, 8> = ConcurrentHashMap::default();
+        let key = map.hash_key("a".to_string());
+
+        assert!(!map.contains_key_hashed(&key));
+        assert_eq!(map.insert_hashed(key.clone(), 1), None);
+        assert_eq!(*map.get("a").unwrap(), 1);
+
+        map.insert("a".to_string(), 2);
+        assert_eq!(*map.get_hashed(&key).unwrap(), 2);
+        assert_eq!(map.remove_hashed(&key), Some(2));
+        assert!(map.get("a").is_none());
+        assert_eq!(key.into_key(), "a");
+    }
+
+    #[test]
+    fn test_hashed_key_conditional_operations() {
+        let map: ConcurrentHashMap<u32, u32, RandomState, 8> = ConcurrentHashMap::default();
+        let key = map.hash_key(1);
+
+        assert_eq!(*map.try_insert_hashed(key, 1).unwrap(), 1);
+        assert_eq!(map.try_insert_hashed(key, 2).unwrap_err().value, 2);
+
+        let value = map.get_or_try_insert_with_hashed(key, || Err(()));
+        assert_eq!(*value.unwrap(), 1);
+        assert_eq!(map.remove_if_hashed(&key, |_, v| *v == 2), None);
+        assert_eq!(map.remove_if_hashed(&key, |_, v| *v == 1), Some((1, 1)));
+
+        let value = map.get_or_try_insert_with_hashed(key, || Ok::<_, ()>(3));
+        assert_eq!(*value.unwrap(), 3);
+        assert_eq!(*map.get(&1).unwrap(), 3);
+    }
+}
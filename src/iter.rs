@@ -0,0 +1,124 @@
+use crate::ConcurrentHashMap;
+use std::ops::Range;
+
+/// An iterator over clones of the entries in a range of shards.
+///
+/// Each shard is read locked only long enough to clone its entries, so no lock is held while
+/// the consumer works on an item. This `struct` is created by
+/// [`ConcurrentHashMap::iter_cloned`] and [`ConcurrentHashMap::split_iter`].
+pub struct IterCloned<'a, K, V, S, const N: usize, P> {
+    map: &'a ConcurrentHashMap<K, V, S, N, P>,
+    shards: Range<usize>,
+    buffer: std::vec::IntoIter<(K, V)>,
+}
+
+impl<K, V, S, const N: usize, P> ConcurrentHashMap<K, V, S, N, P> {
+    /// Returns an iterator over clones of every entry, shard by shard.
+    ///
+    /// Each shard's entries are cloned under its read lock, which is released before any of
+    /// them are yielded, so slow work per item never blocks writers. The result is not a
+    /// consistent snapshot of the whole map.
+    ///
+    /// **Locks** - Acquires a read lock on each of `N` shards, one at a time.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use sharded::ConcurrentHashMap;
+    ///
+    /// let map = ConcurrentHashMap::from([(1, "a"), (2, "b")]);
+    /// for (k, _) in map.iter_cloned() {
+    ///     // no lock is held here
+    ///     map.remove(&k);
+    /// }
+    /// assert!(map.get(&1).is_none());
+    /// ```
+    pub fn iter_cloned(&self) -> IterCloned<'_, K, V, S, N, P> {
+        self.iter_shards(0..N)
+    }
+
+    /// Splits iteration into up to `n` iterators over disjoint, contiguous ranges of shards,
+    /// for scanning the map in parallel on threads of the caller's choosing.
+    ///
+    /// Fewer than `n` iterators are returned if there are fewer shards than that.
+    ///
+    /// **Locks** - Each iterator acquires a read lock on each of its shards, one at a time.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use sharded::ConcurrentHashMap;
+    ///
+    /// let map: ConcurrentHashMap<_, _> = (0..1000u64).map(|i| (i, i)).collect();
+    ///
+    /// let total: u64 = std::thread::scope(|s| {
+    ///     let workers: Vec<_> = map
+    ///         .split_iter(4)
+    ///         .into_iter()
+    ///         .map(|iter| s.spawn(move || iter.map(|(_, v)| v).sum::<u64>()))
+    ///         .collect();
+    ///     workers.into_iter().map(|w| w.join().unwrap()).sum()
+    /// });
+    /// assert_eq!(total, 499500);
+    /// ```
+    pub fn split_iter(&self, n: usize) -> Vec<IterCloned<'_, K, V, S, N, P>> {
+        let chunk = N.div_ceil(n.clamp(1, N.max(1)));
+        (0..N)
+            .step_by(chunk.max(1))
+            .map(|start| self.iter_shards(start..(start + chunk).min(N)))
+            .collect()
+    }
+
+    fn iter_shards(&self, shards: Range<usize>) -> IterCloned<'_, K, V, S, N, P> {
+        IterCloned {
+            map: self,
+            shards,
+            buffer: Vec::new().into_iter(),
+        }
+    }
+}
+
+impl<K, V, S, const N: usize, P> Iterator for IterCloned<'_, K, V, S, N, P>
+where
+    K: Clone,
+    V: Clone,
+{
+    type Item = (K, V);
+
+    fn next(&mut self) -> Option<(K, V)> {
+        loop {
+            if let Some(item) = self.buffer.next() {
+                return Some(item);
+            }
+
+            let shard = self.shards.next()?;
+            let entries: Vec<(K, V)> = self.map.shards[shard]
+                .read()
+                .inner
+                .iter()
+                .cloned()
+                .collect();
+            self.buffer = entries.into_iter();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::ConcurrentHashMap;
+    use std::collections::hash_map::RandomState;
+
+    #[test]
+    fn test_split_iter_covers_disjoint_shards() {
+        let map: ConcurrentHashMap<u32, u32, RandomState, 10> = (0..500).map(|i| (i, i)).collect();
+
+        for n in [0, 1, 3, 10, 64] {
+            let iters = map.split_iter(n);
+            assert!(!iters.is_empty() && iters.len() <= n.clamp(1, 10));
+
+            let mut keys: Vec<u32> = iters.into_iter().flatten().map(|(k, _)| k).collect();
+            keys.sort_unstable();
+            assert_eq!(keys, (0..500).collect::<Vec<_>>());
+        }
+    }
+}
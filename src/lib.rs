@@ -14,13 +14,13 @@
 //! ## Features
 //!
 //! * **Zero unsafe code.** This library uses `#![forbid(unsafe_code)]` and was motivated by
-//!     the complexity and amount of memory errors present in many alternatives.
+//!   the complexity and amount of memory errors present in many alternatives.
 //!
-//! * **Tiny footprint.** The core logic is <100 lines of code. The two dependencies are
-//!     `hashbrown` and `parking_lot`.
+//! * **Small footprint.** The only required dependencies are `hashbrown` and `parking_lot`.
+//!   Alternative hashers, `rand` and `serde` support are opt-in features.
 //!
 //! * **Really fast.** This implementation may be a more performant choice than some
-//!     of the most popular concurrent hashmaps out there. Try it on your workload and let us know.
+//!   of the most popular concurrent hashmaps out there. Try it on your workload and let us know.
 //!
 //! ## See Also
 //!
@@ -43,7 +43,7 @@
 //! # use sharded::ConcurrentHashMap;
 //! let users = ConcurrentHashMap::new();
 //! users.insert(32, "Henry");
-//! assert_eq!(&"Henry", users.get(32).unwrap());
+//! assert_eq!("Henry", *users.get(&32).unwrap());
 //! ```
 //!
 //! ## Performance Comparison
@@ -68,7 +68,7 @@
 //! Many thanks to
 //!
 //! - [Reddit community](https://www.reddit.com/r/rust) for a few pointers and
-//! some motivation to take this project further.
+//!   some motivation to take this project further.
 //!
 //! - [Jon Gjengset](https://github.com/jonhoo) for the live streams and utility crates involved
 //!
@@ -84,34 +84,151 @@
 //! dual licensed as above, without any additional terms or conditions.
 #![forbid(unsafe_code)]
 
-use hashbrown::raw::{RawIntoIter, RawTable};
-use parking_lot::{MappedRwLockReadGuard, RwLock, RwLockReadGuard};
-use std::borrow::Borrow;
-use std::convert::TryInto;
+use hashbrown::hash_table::{self, Entry};
+pub use hashbrown::{Equivalent, HashTable, TryReserveError};
+use parking_lot::{RwLockReadGuard, RwLockWriteGuard};
 use std::hash::{BuildHasher, Hash};
+use std::sync::atomic::{AtomicUsize, Ordering};
 use std::{fmt, fmt::Debug};
 
 use std::collections::hash_map::RandomState;
+use std::collections::HashMap;
+
+mod batch;
+mod builder;
+#[cfg(feature = "deadlock_detection")]
+pub mod deadlock;
+mod entry_lock;
+mod handle;
+mod hashed;
+mod iter;
+mod lock;
+mod once;
+mod padded;
+mod raw_entry;
+mod scan;
+mod select;
+mod shards;
+mod singleflight;
+mod striped;
+#[cfg(feature = "lock-timing")]
+mod timing;
+mod transaction;
+mod try_lock;
+mod upgradable;
+mod view;
+mod wait;
+
+pub use batch::{Op, OpResult};
+pub use builder::ConcurrentHashMapBuilder;
+pub use entry_lock::{EntryGuard, EntryLockMap};
+pub use handle::{ReadHandle, WriteHandle};
+pub use hashed::HashedKey;
+pub use iter::IterCloned;
+use lock::{Locked, ShardLock};
+pub use lock::{ReadGuard, ReadPolicy, WriteGuard};
+pub use once::ShardedOnceMap;
+use padded::CachePadded;
+pub use raw_entry::{RawEntryBuilderMut, RawEntryMut, RawOccupiedEntryMut, RawVacantEntryMut};
+pub use scan::Cursor;
+use select::select_shard;
+pub use select::{HashSelector, JumpSelector, MixingSelector, ModuloSelector, ShardSelector};
+pub use shards::ShardRef;
+pub use singleflight::KeyedSingleflight;
+pub use striped::StripedLock;
+#[cfg(feature = "lock-timing")]
+pub use timing::{ContentionStats, LockHistogram};
+pub use transaction::Transaction;
+pub use try_lock::WouldBlock;
+pub use upgradable::UpgradableReadGuard;
+pub use view::ReadView;
+use wait::Notifier;
+#[cfg(feature = "async")]
+pub use wait::WaitFor;
 
 /// Number of shards
 const DEFAULT_SHARD_COUNT: usize = 128;
 
+/// A hash builder with fixed keys, so hashes (and therefore shard placement) are identical
+/// across runs of the same build. Combine with [`ConcurrentHashMap::sorted_entries`] for
+/// reproducible output.
+///
+/// This offers no protection against HashDoS and should only be used for tests.
+#[cfg(feature = "deterministic")]
+pub type DeterministicState =
+    std::hash::BuildHasherDefault<std::collections::hash_map::DefaultHasher>;
+
+/// A [`ConcurrentHashMap`] hashing with `ahash`, which is much faster than the default SipHash
+/// and still randomly keyed.
+///
+/// # Examples
+///
+/// ```
+/// use sharded::AHashConcurrentHashMap;
+///
+/// let map: AHashConcurrentHashMap<_, _> = AHashConcurrentHashMap::default();
+/// map.insert(1, "a");
+/// assert_eq!(*map.get(&1).unwrap(), "a");
+/// ```
+#[cfg(feature = "ahash")]
+pub type AHashConcurrentHashMap<K, V, const N: usize = DEFAULT_SHARD_COUNT> =
+    ConcurrentHashMap<K, V, ahash::RandomState, N>;
+
+/// A [`ConcurrentHashMap`] hashing with `fxhash`, the fast unkeyed hasher used by rustc.
+///
+/// This offers no protection against HashDoS, only use it for trusted keys.
+///
+/// # Examples
+///
+/// ```
+/// use sharded::FxConcurrentHashMap;
+///
+/// let map: FxConcurrentHashMap<_, _> = FxConcurrentHashMap::default();
+/// map.insert(1, "a");
+/// assert_eq!(*map.get(&1).unwrap(), "a");
+/// ```
+#[cfg(feature = "fxhash")]
+pub type FxConcurrentHashMap<K, V, const N: usize = DEFAULT_SHARD_COUNT> =
+    ConcurrentHashMap<K, V, fxhash::FxBuildHasher, N>;
+
+/// Picks an index with probability proportional to its weight, `None` if all are zero
+#[cfg(feature = "rand")]
+fn pick_weighted<R>(weights: &[usize], rng: &mut R) -> Option<usize>
+where
+    R: rand::Rng + ?Sized,
+{
+    let total: usize = weights.iter().sum();
+    if total == 0 {
+        return None;
+    }
+
+    let mut n = rng.random_range(0..total);
+    for (i, &weight) in weights.iter().enumerate() {
+        if n < weight {
+            return Some(i);
+        }
+        n -= weight;
+    }
+
+    None
+}
+
 // From hashbrown
 // Ensures that a single closure type across uses of this which, in turn prevents multiple
-// instances of any functions like RawTable::reserve from being generated
+// instances of any functions like HashTable::reserve from being generated
 #[inline]
-fn equivalent_key<K, V>(k: &K) -> impl Fn(&(K, V)) -> bool + '_
+fn equivalent_key<Q, K, V>(k: &Q) -> impl Fn(&(K, V)) -> bool + '_
 where
-    K: Eq,
+    Q: ?Sized + Equivalent<K>,
 {
-    move |x| k.eq(x.0.borrow())
+    move |x| k.equivalent(&x.0)
 }
 
 // From hashbrown
 #[inline]
-fn make_hash<K, S>(hash_builder: &S, val: &K) -> u64
+fn make_hash<Q, S>(hash_builder: &S, val: &Q) -> u64
 where
-    K: Hash,
+    Q: Hash + ?Sized,
     S: BuildHasher,
 {
     hash_builder.hash_one(val)
@@ -119,7 +236,7 @@ where
 
 // From hashbrown
 // Ensures that a single closure type across uses of this which, in turn prevents multiple
-// instances of any functions like RawTable::reserve from being generated
+// instances of any functions like HashTable::reserve from being generated
 #[inline]
 fn make_hasher<K, V, S>(hash_builder: &S) -> impl Fn(&(K, V)) -> u64 + '_
 where
@@ -130,9 +247,21 @@ where
 }
 
 /// A concurrent lock-based `HashMap` based on `hashbrown` and `parking_lot`.
-pub struct ConcurrentHashMap<K, V, S = RandomState, const N: usize = DEFAULT_SHARD_COUNT> {
+pub struct ConcurrentHashMap<
+    K,
+    V,
+    S = RandomState,
+    const N: usize = DEFAULT_SHARD_COUNT,
+    P = HashSelector,
+> {
     hash_builder: S,
-    shards: [RwLock<Shard<K, V, S>>; N],
+    selector: P,
+    // padded so neighbouring locks don't share a cache line
+    shards: [CachePadded<ShardLock<Shard<K, V>>>; N],
+    // shard where the next `pop` starts looking
+    pop_cursor: AtomicUsize,
+    // wakes `wait_for` callers, one per shard
+    notifiers: [Notifier; N],
 }
 
 impl<K, V> ConcurrentHashMap<K, V, RandomState, DEFAULT_SHARD_COUNT> {
@@ -192,14 +321,12 @@ impl<K, V, S: BuildHasher, const N: usize> ConcurrentHashMap<K, V, S, N> {
     /// use sharded::ConcurrentHashMap;
     /// use std::collections::hash_map::RandomState;
     ///
-    /// let mut map = ConcurrentHashMap::with_hasher(RandomState::new());
+    /// let mut map: ConcurrentHashMap<_, _, RandomState> =
+    ///     ConcurrentHashMap::with_hasher(RandomState::new());
     /// map.insert(1, 2);
     /// ```
     #[inline]
-    pub fn with_hasher(hash_builder: S) -> ConcurrentHashMap<K, V, S, N>
-    where
-        S: Clone,
-    {
+    pub fn with_hasher(hash_builder: S) -> ConcurrentHashMap<K, V, S, N> {
         ConcurrentHashMap::<_, _, _, N>::with_capacity_and_hasher(0, hash_builder)
     }
 
@@ -224,45 +351,168 @@ impl<K, V, S: BuildHasher, const N: usize> ConcurrentHashMap<K, V, S, N> {
     /// use std::collections::hash_map::RandomState;
     ///
     /// let s = RandomState::new();
-    /// let mut map = ConcurrentHashMap::with_capacity_and_hasher(10, s);
+    /// let mut map: ConcurrentHashMap<_, _, RandomState> =
+    ///     ConcurrentHashMap::with_capacity_and_hasher(10, s);
     /// map.insert(1, 2);
     /// ```
     pub fn with_capacity_and_hasher(
         capacity: usize,
         hash_builder: S,
-    ) -> ConcurrentHashMap<K, V, S, N>
+    ) -> ConcurrentHashMap<K, V, S, N> {
+        ConcurrentHashMap::with_capacity_hasher_and_selector(capacity, hash_builder, HashSelector)
+    }
+}
+
+impl<K, V, S: BuildHasher, const N: usize, P> ConcurrentHashMap<K, V, S, N, P> {
+    // evaluated per `N` when a constructor is instantiated, so zero shards fails to compile
+    const NONZERO_SHARDS: () = assert!(N > 0, "number of shards must be > 0");
+
+    /// Creates an empty `ConcurrentHashMap` with the specified capacity, using `hash_builder`
+    /// to hash the keys and `selector` to route them to shards.
+    ///
+    /// See [`ShardSelector`] for an example.
+    pub fn with_capacity_hasher_and_selector(
+        capacity: usize,
+        hash_builder: S,
+        selector: P,
+    ) -> ConcurrentHashMap<K, V, S, N, P> {
+        let () = Self::NONZERO_SHARDS;
+
+        // per shard capacity
+        let capacity = capacity.div_ceil(N);
+
+        let tables = (0..N).map(|_| HashTable::with_capacity(capacity));
+        ConcurrentHashMap::from_tables(tables, hash_builder, selector)
+    }
+
+    /// Like [`with_capacity_hasher_and_selector`](Self::with_capacity_hasher_and_selector), but
+    /// allocates the shards on up to `threads` scoped threads, so startup time for very large
+    /// capacities scales with cores.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use sharded::{ConcurrentHashMap, HashSelector};
+    /// use std::collections::hash_map::RandomState;
+    ///
+    /// let map: ConcurrentHashMap<u64, u64> = ConcurrentHashMap::with_capacity_parallel(
+    ///     1_000_000,
+    ///     RandomState::new(),
+    ///     HashSelector,
+    ///     4,
+    /// );
+    /// assert!(map.capacity() >= 1_000_000);
+    /// ```
+    pub fn with_capacity_parallel(
+        capacity: usize,
+        hash_builder: S,
+        selector: P,
+        threads: usize,
+    ) -> ConcurrentHashMap<K, V, S, N, P>
     where
-        S: Clone,
+        K: Send,
+        V: Send,
     {
+        let () = Self::NONZERO_SHARDS;
+
         // per shard capacity
-        let capacity = (capacity + N - 1) / N;
-
-        let shards: Vec<RwLock<Shard<K, V, S>>> =
-            std::iter::repeat(|| RawTable::with_capacity(capacity))
-                .map(|f| f())
-                .take(N)
-                .map(|inner| {
-                    RwLock::new(Shard {
-                        inner,
-                        hash_builder: hash_builder.clone(),
+        let capacity = capacity.div_ceil(N);
+        let chunk = N.div_ceil(threads.clamp(1, N.max(1))).max(1);
+
+        let tables = std::thread::scope(|scope| {
+            let workers: Vec<_> = (0..N)
+                .step_by(chunk)
+                .map(|start| {
+                    scope.spawn(move || {
+                        (start..(start + chunk).min(N))
+                            .map(|_| HashTable::with_capacity(capacity))
+                            .collect::<Vec<_>>()
                     })
                 })
-                .collect::<Vec<_>>();
+                .collect();
 
-        match shards.try_into() {
-            Ok(shards) => ConcurrentHashMap {
-                hash_builder,
-                shards,
-            },
-            // .unwrap() requires Debug
-            // this never panics because the iter takes exactly DEFAULT_SHARD_COUNT
-            Err(_) => panic!("unable to build inner"),
+            let mut tables = Vec::with_capacity(N);
+            for worker in workers {
+                match worker.join() {
+                    Ok(chunk) => tables.extend(chunk),
+                    Err(panic) => std::panic::resume_unwind(panic),
+                }
+            }
+            tables
+        });
+
+        ConcurrentHashMap::from_tables(tables, hash_builder, selector)
+    }
+
+    /// Wraps `N` tables, in shard order, into a map
+    fn from_tables<I>(tables: I, hash_builder: S, selector: P) -> Self
+    where
+        I: IntoIterator<Item = HashTable<(K, V)>>,
+    {
+        let mut tables = tables.into_iter();
+
+        let map = ConcurrentHashMap {
+            hash_builder,
+            selector,
+            shards: std::array::from_fn(|_| {
+                let inner = tables.next().unwrap_or_default();
+                CachePadded::new(ShardLock::new(Shard { inner }))
+            }),
+            pop_cursor: AtomicUsize::new(0),
+            notifiers: std::array::from_fn(|_| Notifier::default()),
+        };
+        #[cfg(feature = "deadlock_detection")]
+        let map = map.locate_shards();
+        map
+    }
+
+    /// Tells each shard lock its index and where it sits in the map, so a deadlocked thread can
+    /// be traced back to the map and shard it waits on. Offsets survive moving the map.
+    #[cfg(feature = "deadlock_detection")]
+    fn locate_shards(mut self) -> Self {
+        let base = &self as *const Self as usize;
+        for (i, lock) in self.shards.iter_mut().enumerate() {
+            let offset = &**lock as *const ShardLock<_> as usize - base;
+            lock.set_location(i, offset);
+        }
+        self
+    }
+
+    /// Returns the map's [`ReadPolicy`].
+    pub fn read_policy(&self) -> ReadPolicy {
+        self.shards[0].policy()
+    }
+
+    fn set_read_policy(&mut self, policy: ReadPolicy) {
+        for lock in &mut self.shards {
+            lock.set_policy(policy);
         }
     }
 
-    /// Returns the approximate number of elements the map can hold without reallocating.
+    /// Returns a reference to the map's [`BuildHasher`].
     ///
-    /// **Locks** - Acquires a read lock on one of `N` shards.
+    /// # Examples
+    ///
+    /// ```
+    /// use sharded::ConcurrentHashMap;
+    /// use std::hash::BuildHasher;
+    ///
+    /// let map: ConcurrentHashMap<i32, i32> = ConcurrentHashMap::new();
+    /// assert_eq!(map.hasher().hash_one(1), map.hasher().hash_one(1));
+    /// ```
+    #[inline]
+    pub fn hasher(&self) -> &S {
+        &self.hash_builder
+    }
+
+    /// Returns the number of elements the map can hold without any shard reallocating, summed
+    /// over all shards.
+    ///
+    /// This is approximate: keys rarely spread perfectly evenly, so a shard may grow before the
+    /// map as a whole reaches this many elements. See
+    /// [`capacity_per_shard`](Self::capacity_per_shard) for the breakdown.
+    ///
+    /// **Locks** - Acquires a read lock on each of `N` shards, one at a time.
     ///
     /// # Examples
     ///
@@ -274,19 +524,180 @@ impl<K, V, S: BuildHasher, const N: usize> ConcurrentHashMap<K, V, S, N> {
     #[inline]
     pub fn capacity(&self) -> usize {
         self.shards
-            .first()
-            .expect("at least one shard present")
-            .read()
-            .inner
-            .capacity()
-            * N
+            .iter()
+            .map(|lock| lock.read().inner.capacity())
+            .sum()
+    }
+
+    /// Returns the capacity of each shard, in shard index order.
+    ///
+    /// **Locks** - Acquires a read lock on each of `N` shards, one at a time.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use sharded::ConcurrentHashMap;
+    /// use std::collections::hash_map::RandomState;
+    ///
+    /// let map: ConcurrentHashMap<i32, i32, RandomState, 4> =
+    ///     ConcurrentHashMap::with_capacity_and_hasher(100, RandomState::new());
+    /// let per_shard = map.capacity_per_shard();
+    /// assert!(per_shard.iter().all(|&c| c >= 25));
+    /// assert_eq!(per_shard.iter().sum::<usize>(), map.capacity());
+    /// ```
+    pub fn capacity_per_shard(&self) -> [usize; N] {
+        std::array::from_fn(|i| self.shards[i].read().inner.capacity())
+    }
+
+    /// Returns the length, capacity and load factor of each shard, in shard index order, e.g.
+    /// for charting the distribution to validate hasher and shard count choices.
+    ///
+    /// **Locks** - Acquires a read lock on each of `N` shards, one at a time.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use sharded::ConcurrentHashMap;
+    /// use std::collections::hash_map::RandomState;
+    ///
+    /// let map: ConcurrentHashMap<_, _, RandomState, 4> = (0..100).map(|i| (i, i)).collect();
+    /// let stats = map.shard_stats();
+    /// assert_eq!(stats.iter().map(|s| s.len).sum::<usize>(), 100);
+    /// assert!(stats.iter().all(|s| s.load_factor <= 1.0));
+    /// ```
+    pub fn shard_stats(&self) -> [ShardStats; N] {
+        std::array::from_fn(|i| {
+            let shard = self.shards[i].read();
+            let (len, capacity) = (shard.len(), shard.inner.capacity());
+            ShardStats {
+                len,
+                capacity,
+                load_factor: if capacity == 0 {
+                    0.0
+                } else {
+                    len as f64 / capacity as f64
+                },
+            }
+        })
+    }
+
+    /// Returns `true` if the largest shard holds more than `factor` times as many entries as
+    /// the median shard (treated as at least 1), a sign that the key distribution defeats the
+    /// hasher or selector. See [`rebalance_with_hasher`](Self::rebalance_with_hasher).
+    ///
+    /// **Locks** - Acquires a read lock on each of `N` shards, one at a time.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use sharded::ConcurrentHashMap;
+    ///
+    /// let map: ConcurrentHashMap<_, _> = (0..10_000).map(|i| (i, i)).collect();
+    /// assert!(!map.is_skewed(20));
+    /// ```
+    pub fn is_skewed(&self, factor: usize) -> bool {
+        let mut lens: [usize; N] = std::array::from_fn(|i| self.shards[i].read().len());
+        lens.sort_unstable();
+
+        match (lens.get(N / 2), lens.last()) {
+            (Some(&median), Some(&largest)) => largest > median.max(1).saturating_mul(factor),
+            _ => false,
+        }
+    }
+
+    /// Reserves capacity for at least `additional` more elements, spread evenly over the
+    /// shards.
+    ///
+    /// Keys are not distributed perfectly evenly, so a shard may still grow before the map
+    /// holds `additional` more elements.
+    ///
+    /// **Locks** - Acquires a write lock on each of `N` shards, one at a time.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the new allocation size overflows `usize`.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use sharded::ConcurrentHashMap;
+    ///
+    /// let map: ConcurrentHashMap<i32, i32> = ConcurrentHashMap::new();
+    /// map.reserve(1000);
+    /// assert!(map.capacity() >= 1000);
+    /// ```
+    pub fn reserve(&self, additional: usize)
+    where
+        K: Hash,
+    {
+        let per_shard = additional.div_ceil(N);
+        for lock in &self.shards {
+            lock.write().reserve(per_shard, &self.hash_builder);
+        }
+    }
+
+    /// Tries to reserve capacity for at least `additional` more elements, spread evenly over
+    /// the shards.
+    ///
+    /// Stops at the first shard that fails to allocate, shards before it keep their new
+    /// capacity.
+    ///
+    /// **Locks** - Acquires a write lock on each of `N` shards, one at a time.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use sharded::ConcurrentHashMap;
+    ///
+    /// let map: ConcurrentHashMap<i32, i32> = ConcurrentHashMap::new();
+    /// assert!(map.try_reserve(1000).is_ok());
+    /// assert!(map.try_reserve(usize::MAX).is_err());
+    /// ```
+    pub fn try_reserve(&self, additional: usize) -> Result<(), TryReserveError>
+    where
+        K: Hash,
+    {
+        let per_shard = additional.div_ceil(N);
+        for lock in &self.shards {
+            lock.write().try_reserve(per_shard, &self.hash_builder)?;
+        }
+        Ok(())
+    }
+
+    /// Returns a snapshot of the map's effective configuration, for operational tooling and
+    /// bug reports. With the `serde` feature enabled it can be serialized.
+    ///
+    /// **Locks** - Acquires a read lock on each of `N` shards, one at a time, to sum the
+    /// capacity.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use sharded::{ConcurrentHashMap, ReadPolicy};
+    /// use std::collections::hash_map::RandomState;
+    ///
+    /// let map: ConcurrentHashMap<u64, u64, RandomState, 16> = ConcurrentHashMap::default();
+    /// let config = map.config();
+    /// assert_eq!(config.shard_count, 16);
+    /// assert!(config.hasher.ends_with("RandomState"));
+    /// assert!(config.selector.ends_with("HashSelector"));
+    /// assert_eq!(config.read_policy, ReadPolicy::Fair);
+    /// ```
+    pub fn config(&self) -> MapConfig {
+        MapConfig {
+            shard_count: N,
+            hasher: std::any::type_name::<S>(),
+            selector: std::any::type_name::<P>(),
+            read_policy: self.read_policy(),
+            capacity: self.capacity(),
+        }
     }
 
     /// Returns a guarded reference for the value corresponding to the
     /// provided key.
     ///
-    /// The key may be any borrowed form of the map's key type, but
-    /// [`Hash`] and [`Eq`] on the borrowed form *must* match those for
+    /// The key may be any borrowed form of the map's key type, or any type implementing
+    /// [`Equivalent`] for it, but [`Hash`] and equality on that form *must* match those for
     /// the key type.
     ///
     /// # Examples
@@ -296,237 +707,2870 @@ impl<K, V, S: BuildHasher, const N: usize> ConcurrentHashMap<K, V, S, N> {
     ///
     /// let mut map = ConcurrentHashMap::new();
     /// map.insert(1, "a");
-    /// assert_eq!(map.get(&1), Some(&"a"));
-    /// assert_eq!(map.get(&2), None);
+    /// assert_eq!(*map.get(&1).unwrap(), "a");
+    /// assert!(map.get(&2).is_none());
     /// ```
     #[inline]
-    pub fn get<'a>(&'a self, key: &'a K) -> Option<MappedRwLockReadGuard<'_, V>>
+    pub fn get<Q>(&self, key: &Q) -> Option<ReadGuard<'_, V>>
     where
-        K: Hash + Eq,
+        Q: Hash + Equivalent<K> + ?Sized,
+        P: ShardSelector<Q>,
     {
-        let hash = make_hash::<K, _>(&self.hash_builder, key);
+        let hash = make_hash::<Q, _>(&self.hash_builder, key);
 
-        let i = hash as usize % N;
+        let i = self.shard_for(hash, key);
 
-        let shard = match self.shards.get(i) {
-            Some(lock) => lock.read(),
-            None => panic!("index out of bounds"),
-        };
+        let shard = self.shards[i].read();
 
-        RwLockReadGuard::try_map(shard, |shard| {
-            match shard.inner.get(hash, equivalent_key(key)) {
-                Some((_, v)) => Some(v),
-                _ => None,
-            }
+        shard.try_map(|shard| match shard.inner.find(hash, equivalent_key(key)) {
+            Some((_, v)) => Some(v),
+            _ => None,
         })
-        .ok()
     }
 
-    /// Insert a key value pair into the Map. Returns the existing
-    /// value at the provided key if there was one.
+    /// Returns a clone of the value corresponding to the key.
+    ///
+    /// Unlike [`get`](Self::get) the shard lock is released before returning, so the value can
+    /// be held across slow work or `.await` points.
+    ///
+    /// **Locks** - Acquires a read lock on one of `N` shards.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use sharded::ConcurrentHashMap;
+    ///
+    /// let map = ConcurrentHashMap::new();
+    /// map.insert(1, String::from("a"));
+    /// assert_eq!(map.get_cloned(&1), Some(String::from("a")));
+    /// assert_eq!(map.get_cloned(&2), None);
+    /// ```
     #[inline]
-    pub fn insert(&self, k: K, v: V) -> Option<V>
+    pub fn get_cloned<Q>(&self, key: &Q) -> Option<V>
     where
-        K: Hash + Eq,
+        Q: Hash + Equivalent<K> + ?Sized,
+        V: Clone,
+        P: ShardSelector<Q>,
     {
-        let hash = make_hash::<K, _>(&self.hash_builder, &k);
+        let hash = make_hash::<Q, _>(&self.hash_builder, key);
 
-        let i = hash as usize % N;
+        let i = self.shard_for(hash, key);
 
-        let mut shard = match self.shards.get(i) {
-            Some(lock) => lock.write(),
-            None => panic!("index out of bounds"),
-        };
-
-        shard.insert(hash, k, v)
+        self.shards[i].read().get(hash, key).cloned()
     }
-}
 
-impl<K, V, S, const N: usize> Default for ConcurrentHashMap<K, V, S, N>
-where
-    S: Default + BuildHasher + Clone,
-{
-    /// Creates an empty `ConcurrentHashMap<K, V, S, N>`, with the `Default` value for the hasher
-    /// and 128 for the Shard Count.
+    /// Calls `f` with a reference to the value corresponding to the key and returns its result.
+    ///
+    /// The shard lock is only held while `f` runs, so no guard can escape or be held across an
+    /// `.await`.
+    ///
+    /// **Locks** - Acquires a read lock on one of `N` shards.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use sharded::ConcurrentHashMap;
+    ///
+    /// let map = ConcurrentHashMap::new();
+    /// map.insert(1, String::from("abc"));
+    /// assert_eq!(map.with(&1, |v| v.len()), Some(3));
+    /// assert_eq!(map.with(&2, |v| v.len()), None);
+    /// ```
     #[inline]
-    fn default() -> ConcurrentHashMap<K, V, S, N> {
-        if N == 0 {
-            panic!("number of shards must be > 0")
-        }
-        ConcurrentHashMap::<K, V, S, N>::with_hasher(Default::default())
+    pub fn with<Q, F, U>(&self, key: &Q, f: F) -> Option<U>
+    where
+        Q: Hash + Equivalent<K> + ?Sized,
+        F: FnOnce(&V) -> U,
+        P: ShardSelector<Q>,
+    {
+        let hash = make_hash::<Q, _>(&self.hash_builder, key);
+
+        let i = self.shard_for(hash, key);
+
+        let shard = self.shards[i].read();
+        shard.get(hash, key).map(f)
     }
-}
 
-/// An owning iterator over the entries of a `ConcurrentHashMap`.
-///
-/// This `struct` is created by the [`into_iter`] method on [`ConcurrentHashMap`]
-/// (provided by the [`IntoIterator`] trait). See its documentation for more.
-///
-/// [`into_iter`]: IntoIterator::into_iter
-/// [`IntoIterator`]: crate::iter::IntoIterator
-///
-/// # Example
-///
-/// ```
-/// use sharded::ConcurrentHashMap;
-///
-/// let map = ConcurrentHashMap::from([
-///     ("a", 1),
-/// ]);
-/// let iter = map.into_iter();
-/// ```
-pub struct IntoIter<K: 'static, V: 'static> {
-    iter: RawIntoIter<(K, V)>,
-    shards: Vec<Shard<K, V>>,
-}
+    /// Returns guarded references for several keys at once, in the order the keys were given.
+    ///
+    /// The keys are grouped by shard and the needed shard read locks are acquired in ascending
+    /// shard index order, the same order used by every multi-shard operation in this crate, so
+    /// the call cannot deadlock against them.
+    ///
+    /// **Locks** - Acquires a read lock on each shard holding one of the keys, held for as long
+    /// as the corresponding guards are alive.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use sharded::ConcurrentHashMap;
+    ///
+    /// let map = ConcurrentHashMap::new();
+    /// map.insert(1, "a");
+    /// map.insert(2, "b");
+    ///
+    /// let [a, b, c] = map.get_many([&1, &2, &3]);
+    /// assert_eq!(*a.unwrap(), "a");
+    /// assert_eq!(*b.unwrap(), "b");
+    /// assert!(c.is_none());
+    /// ```
+    pub fn get_many<Q, const M: usize>(&self, keys: [&Q; M]) -> [Option<ReadGuard<'_, V>>; M]
+    where
+        Q: Hash + Equivalent<K> + ?Sized,
+        P: ShardSelector<Q>,
+    {
+        let hashes = keys.map(|key| make_hash::<Q, _>(&self.hash_builder, key));
 
-pub struct IntoValues<K: 'static, V: 'static> {
-    iter: IntoIter<K, V>,
-}
+        let mut order: [usize; M] = std::array::from_fn(|i| i);
+        order.sort_unstable_by_key(|&i| self.shard_for(hashes[i], keys[i]));
 
-impl<K, V> Iterator for IntoIter<K, V> {
-    type Item = (K, V);
+        let mut guards: [Option<ShardReadGuard<'_, K, V>>; M] = std::array::from_fn(|_| None);
 
-    #[inline]
-    fn next(&mut self) -> Option<(K, V)> {
-        match self.iter.next() {
-            Some(item) => Some(item),
-            None => match self.shards.pop() {
-                Some(s) => {
-                    self.iter = s.inner.into_iter();
-                    self.next()
-                }
-                None => None,
-            },
+        let mut previous = None;
+        for i in order {
+            let shard = self.shard_for(hashes[i], keys[i]);
+            // a shard that is already read locked by this call must be locked recursively, or
+            // a queued writer would deadlock us against ourselves
+            guards[i] = Some(if previous == Some(shard) {
+                self.shards[shard].read_recursive()
+            } else {
+                self.shards[shard].read()
+            });
+            previous = Some(shard);
         }
-    }
 
-    #[inline]
-    fn size_hint(&self) -> (usize, Option<usize>) {
-        (self.iter.size_hint().0, None)
+        let mut guards = guards.into_iter();
+        std::array::from_fn(|i| {
+            let guard = guards.next().flatten()?;
+            guard.try_map(|shard| shard.get(hashes[i], keys[i]))
+        })
     }
-}
-
-impl<K, V> Iterator for IntoValues<K, V> {
-    type Item = V;
 
-    #[inline]
-    fn next(&mut self) -> Option<V> {
-        self.iter.next().map(|(_, v)| v)
+    /// Returns a guarded reference to the key value pair corresponding to the provided key.
+    ///
+    /// Useful when keys compare equal on a subset of their data and the stored key itself is
+    /// needed.
+    ///
+    /// **Locks** - Acquires a read lock on one of `N` shards, which is held for as long as the
+    /// returned guard is alive.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use sharded::ConcurrentHashMap;
+    ///
+    /// let map = ConcurrentHashMap::new();
+    /// map.insert(1, "a");
+    /// assert_eq!(*map.get_key_value(&1).unwrap(), (1, "a"));
+    /// assert!(map.get_key_value(&2).is_none());
+    /// ```
+    #[inline]
+    pub fn get_key_value<Q>(&self, key: &Q) -> Option<ReadGuard<'_, (K, V)>>
+    where
+        Q: Hash + Equivalent<K> + ?Sized,
+        P: ShardSelector<Q>,
+    {
+        let hash = make_hash::<Q, _>(&self.hash_builder, key);
+
+        let i = self.shard_for(hash, key);
+
+        let shard = self.shards[i].read();
+
+        shard.try_map(|shard| shard.inner.find(hash, equivalent_key(key)))
     }
 
+    /// Returns `true` if the map contains a value for the specified key.
+    ///
+    /// The key may be any borrowed form of the map's key type, or any type implementing
+    /// [`Equivalent`] for it, but [`Hash`] and equality on that form *must* match those for
+    /// the key type.
+    ///
+    /// **Locks** - Acquires a read lock on one of `N` shards.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use sharded::ConcurrentHashMap;
+    ///
+    /// let map = ConcurrentHashMap::new();
+    /// map.insert(String::from("a"), 1);
+    /// assert!(map.contains_key("a"));
+    /// assert!(!map.contains_key("b"));
+    /// ```
     #[inline]
-    fn size_hint(&self) -> (usize, Option<usize>) {
-        (self.iter.size_hint().0, None)
-    }
-}
+    pub fn contains_key<Q>(&self, key: &Q) -> bool
+    where
+        Q: Hash + Equivalent<K> + ?Sized,
+        P: ShardSelector<Q>,
+    {
+        let hash = make_hash::<Q, _>(&self.hash_builder, key);
 
-/// A single shard in the map
-#[derive(Clone)]
-pub(crate) struct Shard<K, V, S = RandomState> {
-    hash_builder: S,
-    inner: RawTable<(K, V)>,
-}
+        let i = self.shard_for(hash, key);
 
-impl<K, V> Debug for Shard<K, V>
-where
-    K: Debug,
-    V: Debug,
-{
-    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
-        f.debug_struct("shard").finish()
+        self.shards[i].read().get(hash, key).is_some()
     }
-}
 
-#[allow(dead_code)]
-impl<K, V, S> Shard<K, V, S>
-where
-    S: BuildHasher,
-{
-    /// Number of items in the shard
+    /// Returns a guarded mutable reference to the value corresponding to the key.
+    ///
+    /// The key may be any borrowed form of the map's key type, or any type implementing
+    /// [`Equivalent`] for it, but [`Hash`] and equality on that form *must* match those for
+    /// the key type.
+    ///
+    /// **Locks** - Acquires a write lock on one of `N` shards, which is held for as long as the
+    /// returned guard is alive.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use sharded::ConcurrentHashMap;
+    ///
+    /// let map = ConcurrentHashMap::new();
+    /// map.insert(String::from("a"), 1);
+    /// *map.get_mut("a").unwrap() += 1;
+    /// assert_eq!(*map.get("a").unwrap(), 2);
+    /// ```
     #[inline]
-    pub(crate) fn len(&self) -> usize {
-        self.inner.len()
+    pub fn get_mut<Q>(&self, key: &Q) -> Option<WriteGuard<'_, V>>
+    where
+        Q: Hash + Equivalent<K> + ?Sized,
+        P: ShardSelector<Q>,
+    {
+        let hash = make_hash::<Q, _>(&self.hash_builder, key);
+
+        let i = self.shard_for(hash, key);
+
+        let shard = self.shards[i].write();
+
+        shard.try_map(|shard| shard.get_mut(hash, key)).ok()
     }
 
-    /// Is `len == 0`
+    /// Insert a key value pair into the Map. Returns the existing
+    /// value at the provided key if there was one.
     #[inline]
-    pub(crate) fn is_empty(&self) -> bool {
-        self.inner.len() == 0
+    pub fn insert(&self, k: K, v: V) -> Option<V>
+    where
+        K: Hash + Eq,
+        P: ShardSelector<K>,
+    {
+        let hash = make_hash::<K, _>(&self.hash_builder, &k);
+
+        let i = self.shard_for(hash, &k);
+
+        let mut shard = self.shards[i].write();
+
+        let previous = shard.insert(hash, k, v, &self.hash_builder);
+        self.notifiers[i].notify();
+        previous
     }
 
-    /// Remove the key, returning the value at that position if it existed
-    #[inline]
-    pub(crate) fn remove(&mut self, hash: u64, key: K) -> Option<V>
+    /// Inserts a key value pair and returns a read guard on the stored value, replacing any
+    /// existing value.
+    ///
+    /// The shard's write lock is atomically downgraded after the insert, so other readers are
+    /// let in while the value is held and no writer can change it in between.
+    ///
+    /// **Locks** - Acquires a write lock on one of `N` shards, downgraded to a read lock for
+    /// the returned guard.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use sharded::ConcurrentHashMap;
+    ///
+    /// let map = ConcurrentHashMap::new();
+    /// let value = map.insert_and_get("a", vec![1, 2, 3]);
+    /// assert_eq!(value.len(), 3);
+    /// drop(value);
+    /// assert_eq!(map.get("a").unwrap().len(), 3);
+    /// ```
+    pub fn insert_and_get(&self, k: K, v: V) -> ReadGuard<'_, V>
     where
         K: Hash + Eq,
+        P: ShardSelector<K>,
     {
-        #[allow(clippy::manual_map)] // reduce compiler IR, I think!
-        match self.inner.remove_entry(hash, equivalent_key(&key)) {
-            Some((_, v)) => Some(v),
-            None => None,
+        let hash = make_hash::<K, _>(&self.hash_builder, &k);
+
+        let i = self.shard_for(hash, &k);
+
+        let mut shard = self.shards[i].write();
+
+        let index = match shard.inner.entry(
+            hash,
+            equivalent_key(&k),
+            make_hasher::<K, V, S>(&self.hash_builder),
+        ) {
+            Entry::Occupied(mut entry) => {
+                entry.get_mut().1 = v;
+                entry.bucket_index()
+            }
+            Entry::Vacant(entry) => entry.insert((k, v)).bucket_index(),
+        };
+        self.notifiers[i].notify();
+
+        downgrade_to_bucket(shard, index)
+    }
+
+    /// Inserts every pair from `iter`, overwriting existing values.
+    ///
+    /// All keys are hashed and grouped by shard up front, so each shard's lock is taken once
+    /// for the whole batch instead of once per pair. The batch is not atomic: readers may see
+    /// some shards updated before others.
+    ///
+    /// **Locks** - Acquires a write lock on each shard receiving pairs, one at a time.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use sharded::ConcurrentHashMap;
+    ///
+    /// let map = ConcurrentHashMap::new();
+    /// map.insert_batch((0..1000).map(|i| (i, i * 2)));
+    /// assert_eq!(*map.get(&500).unwrap(), 1000);
+    /// ```
+    pub fn insert_batch<I>(&self, iter: I)
+    where
+        K: Hash + Eq,
+        I: IntoIterator<Item = (K, V)>,
+        P: ShardSelector<K>,
+    {
+        for (i, batch) in self.group_by_shard(iter).into_iter().enumerate() {
+            if batch.is_empty() {
+                continue;
+            }
+
+            let mut shard = self.shards[i].write();
+            shard.reserve(batch.len(), &self.hash_builder);
+            for (hash, k, v) in batch {
+                shard.insert(hash, k, v, &self.hash_builder);
+            }
+            self.notifiers[i].notify();
         }
     }
 
-    /// Get mutable value for the provided key
-    #[inline]
-    pub(crate) fn get_mut(&mut self, hash: u64, key: &K) -> Option<&mut V>
+    /// Moves every entry of `other` into this map, calling `f` with the key, the existing
+    /// value and the incoming value to resolve keys present in both.
+    ///
+    /// Entries are grouped by destination shard, so each shard's lock is taken once. `other`
+    /// may use a different hasher or shard count.
+    ///
+    /// **Locks** - Acquires a write lock on each shard receiving entries, one at a time.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use sharded::ConcurrentHashMap;
+    ///
+    /// let global = ConcurrentHashMap::from([("a", 1), ("b", 2)]);
+    /// let worker = ConcurrentHashMap::from([("b", 10), ("c", 3)]);
+    ///
+    /// global.merge(worker, |_, mine, theirs| mine + theirs);
+    /// assert_eq!(*global.get(&"b").unwrap(), 12);
+    /// assert_eq!(*global.get(&"c").unwrap(), 3);
+    /// ```
+    pub fn merge<S2, const M: usize, P2, F>(
+        &self,
+        other: ConcurrentHashMap<K, V, S2, M, P2>,
+        mut f: F,
+    ) where
+        K: Hash + Eq,
+        F: FnMut(&K, &V, V) -> V,
+        P: ShardSelector<K>,
+    {
+        for (i, batch) in self.group_by_shard(other).into_iter().enumerate() {
+            if batch.is_empty() {
+                continue;
+            }
+
+            let mut shard = self.shards[i].write();
+            for (hash, k, theirs) in batch {
+                match shard.get_mut(hash, &k) {
+                    Some(mine) => *mine = f(&k, mine, theirs),
+                    None => {
+                        shard.insert_unique(hash, k, theirs, &self.hash_builder);
+                    }
+                }
+            }
+            self.notifiers[i].notify();
+        }
+    }
+
+    /// Moves every entry into `target`, overwriting values for keys it already holds.
+    ///
+    /// Each source shard is drained under its own lock, which is released before the entries
+    /// are inserted into `target` shard by shard, so no two locks are ever held at once. While
+    /// a shard's entries are in flight they are visible in neither map.
+    ///
+    /// Keys are rehashed with `target`'s hasher: hash builders such as `RandomState` cannot be
+    /// compared, so there is no way to tell whether stored hashes would still be valid.
+    ///
+    /// **Locks** - Acquires a write lock on each of `N` shards, then on each `target` shard
+    /// receiving entries, one at a time.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use sharded::ConcurrentHashMap;
+    ///
+    /// let current = ConcurrentHashMap::from([("a", 1), ("b", 2)]);
+    /// let previous = ConcurrentHashMap::from([("a", 0)]);
+    ///
+    /// current.drain_into(&previous);
+    /// assert!(current.get(&"a").is_none());
+    /// assert_eq!(*previous.get(&"a").unwrap(), 1);
+    /// assert_eq!(*previous.get(&"b").unwrap(), 2);
+    /// ```
+    pub fn drain_into<S2, const M: usize, P2>(&self, target: &ConcurrentHashMap<K, V, S2, M, P2>)
     where
         K: Hash + Eq,
+        S2: BuildHasher,
+        P2: ShardSelector<K>,
     {
-        match self.inner.get_mut(hash, equivalent_key(key)) {
-            Some(&mut (_, ref mut v)) => Some(v),
-            None => None,
+        for lock in &self.shards {
+            let entries: Vec<(K, V)> = lock.write().inner.drain().collect();
+            if !entries.is_empty() {
+                target.insert_batch(entries);
+            }
         }
     }
 
-    /// Insert the key value pair
+    /// Hashes every pair and buckets it by shard index
+    fn group_by_shard<I>(&self, iter: I) -> Vec<Vec<(u64, K, V)>>
+    where
+        K: Hash,
+        I: IntoIterator<Item = (K, V)>,
+        P: ShardSelector<K>,
+    {
+        let mut batches: Vec<Vec<(u64, K, V)>> = (0..N).map(|_| Vec::new()).collect();
+        for (k, v) in iter {
+            let hash = make_hash::<K, _>(&self.hash_builder, &k);
+            batches[self.shard_for(hash, &k)].push((hash, k, v));
+        }
+        batches
+    }
+
+    /// Tries to insert a key value pair into the map, returning a guard for the inserted value.
+    ///
+    /// If the map already had this key present, nothing is updated, and an error containing
+    /// the rejected value and a guard for the existing value is returned.
+    ///
+    /// **Locks** - Acquires a write lock on one of `N` shards, which is held for as long as the
+    /// returned guard (or error) is alive.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use sharded::ConcurrentHashMap;
+    ///
+    /// let map = ConcurrentHashMap::new();
+    /// assert_eq!(*map.try_insert(37, "a").unwrap(), "a");
+    ///
+    /// let err = map.try_insert(37, "b").unwrap_err();
+    /// assert_eq!(*err.existing, "a");
+    /// assert_eq!(err.value, "b");
+    /// ```
     #[inline]
-    pub(crate) fn insert(&mut self, hash: u64, key: K, v: V) -> Option<V>
+    pub fn try_insert(&self, k: K, v: V) -> Result<WriteGuard<'_, V>, OccupiedError<'_, V>>
     where
         K: Hash + Eq,
+        P: ShardSelector<K>,
     {
-        if let Some((_, item)) = self.inner.get_mut(hash, equivalent_key(&key)) {
-            Some(std::mem::replace(item, v))
-        } else {
-            self.inner
-                .insert(hash, (key, v), make_hasher::<K, V, S>(&self.hash_builder));
-            None
-        }
+        self.try_insert_hashed(self.hash_key(k), v)
     }
 
-    /// Get the value for the key if it exists
+    /// Appends `item` to the collection stored at `key`, creating an empty one first if the key
+    /// is absent.
+    ///
+    /// Intended for maps whose values are `Vec<T>` (or any other `Default + Extend<T>`
+    /// collection), it hashes once and appends under a single write lock.
+    ///
+    /// **Locks** - Acquires a write lock on one of `N` shards.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use sharded::ConcurrentHashMap;
+    ///
+    /// let logs: ConcurrentHashMap<&str, Vec<&str>> = ConcurrentHashMap::new();
+    /// logs.push_value("api", "started");
+    /// logs.push_value("api", "stopped");
+    /// assert_eq!(logs.take_values(&"api"), vec!["started", "stopped"]);
+    /// ```
     #[inline]
-    pub(crate) fn get(&self, hash: u64, key: &K) -> Option<&V>
+    pub fn push_value<T>(&self, key: K, item: T)
     where
         K: Hash + Eq,
+        V: Default + Extend<T>,
+        P: ShardSelector<K>,
     {
-        match self.inner.get(hash, equivalent_key(key)) {
-            Some((_, v)) => Some(v),
-            None => None,
-        }
+        let hash = make_hash::<K, _>(&self.hash_builder, &key);
+
+        let i = self.shard_for(hash, &key);
+
+        let mut shard = self.shards[i].write();
+
+        shard
+            .get_or_insert_with(hash, key, V::default, &self.hash_builder)
+            .extend(std::iter::once(item));
+        self.notifiers[i].notify();
     }
-}
 
-#[cfg(test)]
-mod tests {
-    use super::*;
-    use std::sync::Arc;
-    use std::time::Duration;
+    /// Returns a guarded reference to the value at `key`, first inserting the result of `f` if
+    /// the key is absent. If `f` fails, nothing is inserted and its error is returned.
+    ///
+    /// `f` runs while the shard is write locked, so concurrent callers for the same key never
+    /// both initialize it. The lock is then downgraded, so the returned guard only blocks
+    /// writers.
+    ///
+    /// **Locks** - Acquires a read lock on one of `N` shards, or a write lock (downgraded to a
+    /// read lock) if the key is absent.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use sharded::ConcurrentHashMap;
+    ///
+    /// let cache: ConcurrentHashMap<u32, String> = ConcurrentHashMap::new();
+    ///
+    /// let failed = cache.get_or_try_insert_with(1, || Err("database unavailable"));
+    /// assert!(failed.is_err());
+    /// assert!(!cache.contains_key(&1));
+    ///
+    /// let loaded = cache.get_or_try_insert_with(1, || Ok::<_, &str>("row".to_string()));
+    /// assert_eq!(*loaded.unwrap(), "row");
+    /// ```
+    pub fn get_or_try_insert_with<F, E>(&self, key: K, f: F) -> Result<ReadGuard<'_, V>, E>
+    where
+        K: Hash + Eq,
+        F: FnOnce() -> Result<V, E>,
+        P: ShardSelector<K>,
+    {
+        self.get_or_try_insert_with_hashed(self.hash_key(key), f)
+    }
 
-    #[test]
-    fn test_apprx_capacity() {
-        let _map: ConcurrentHashMap<usize, usize, RandomState, 120> =
-            ConcurrentHashMap::with_capacity_and_hasher(1000, RandomState::new());
+    /// Removes the collection stored at `key` and returns it, or an empty collection if the
+    /// key is absent. The counterpart to [`push_value`](Self::push_value).
+    ///
+    /// **Locks** - Acquires a write lock on one of `N` shards.
+    #[inline]
+    pub fn take_values<Q>(&self, key: &Q) -> V
+    where
+        Q: Hash + Equivalent<K> + ?Sized,
+        V: Default,
+        P: ShardSelector<Q>,
+    {
+        self.remove(key).unwrap_or_default()
     }
 
-    #[test]
-    fn test_insert_values() {
-        let map = ConcurrentHashMap::new();
-        {
-            map.insert("k", "v");
+    /// Removes a key from the map, returning the value at the key if the key was previously
+    /// in the map.
+    ///
+    /// The key may be any borrowed form of the map's key type, or any type implementing
+    /// [`Equivalent`] for it, but [`Hash`] and equality on that form *must* match those for
+    /// the key type.
+    ///
+    /// **Locks** - Acquires a write lock on one of `N` shards.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use sharded::ConcurrentHashMap;
+    ///
+    /// let map = ConcurrentHashMap::new();
+    /// map.insert(String::from("a"), 1);
+    /// assert_eq!(map.remove("a"), Some(1));
+    /// assert_eq!(map.remove("a"), None);
+    /// ```
+    #[inline]
+    pub fn remove<Q>(&self, key: &Q) -> Option<V>
+    where
+        Q: Hash + Equivalent<K> + ?Sized,
+        P: ShardSelector<Q>,
+    {
+        let hash = make_hash::<Q, _>(&self.hash_builder, key);
+
+        let i = self.shard_for(hash, key);
+
+        let mut shard = self.shards[i].write();
+
+        shard.remove(hash, key)
+    }
+
+    /// Removes every key in `keys`, returning the removed values in the same order.
+    ///
+    /// Keys are grouped by shard up front, so each shard's lock is taken once for the whole
+    /// batch. A key listed twice is removed once, the second slot is `None`.
+    ///
+    /// **Locks** - Acquires a write lock on each shard holding one of the keys, one at a time.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use sharded::ConcurrentHashMap;
+    ///
+    /// let map = ConcurrentHashMap::new();
+    /// map.insert_batch([(1, "a"), (2, "b"), (3, "c")]);
+    ///
+    /// assert_eq!(map.remove_batch(&[&3, &4, &1]), vec![Some("c"), None, Some("a")]);
+    /// assert!(map.contains_key(&2));
+    /// ```
+    pub fn remove_batch<Q>(&self, keys: &[&Q]) -> Vec<Option<V>>
+    where
+        Q: Hash + Equivalent<K> + ?Sized,
+        P: ShardSelector<Q>,
+    {
+        let mut batches: Vec<Vec<(usize, u64)>> = (0..N).map(|_| Vec::new()).collect();
+        for (position, key) in keys.iter().enumerate() {
+            let hash = make_hash::<Q, _>(&self.hash_builder, *key);
+            batches[self.shard_for(hash, *key)].push((position, hash));
+        }
+
+        let mut removed: Vec<Option<V>> = keys.iter().map(|_| None).collect();
+        for (lock, batch) in self.shards.iter().zip(batches) {
+            if batch.is_empty() {
+                continue;
+            }
+
+            let mut shard = lock.write();
+            for (position, hash) in batch {
+                removed[position] = shard.remove(hash, keys[position]);
+            }
+        }
+
+        removed
+    }
+
+    /// Takes ownership of the value at `key`, removing it from the map.
+    ///
+    /// This is the same operation as [`remove`](Self::remove), named for claim or checkout
+    /// patterns where exactly one caller should end up owning the value.
+    ///
+    /// **Locks** - Acquires a write lock on one of `N` shards.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use sharded::ConcurrentHashMap;
+    ///
+    /// let jobs = ConcurrentHashMap::new();
+    /// jobs.insert(1, "resize images");
+    /// assert_eq!(jobs.take(&1), Some("resize images"));
+    /// assert_eq!(jobs.take(&1), None);
+    /// ```
+    #[inline]
+    pub fn take<Q>(&self, key: &Q) -> Option<V>
+    where
+        Q: Hash + Equivalent<K> + ?Sized,
+        P: ShardSelector<Q>,
+    {
+        self.remove(key)
+    }
+
+    /// Takes ownership of the value at `key` only if `pred` returns `true` for it.
+    ///
+    /// The check and the removal happen under one write lock, so the value cannot change in
+    /// between.
+    ///
+    /// **Locks** - Acquires a write lock on one of `N` shards.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use sharded::ConcurrentHashMap;
+    ///
+    /// let jobs = ConcurrentHashMap::new();
+    /// jobs.insert(1, (3, "resize images"));
+    /// assert_eq!(jobs.take_if(&1, |(priority, _)| *priority > 5), None);
+    /// assert_eq!(jobs.take_if(&1, |(priority, _)| *priority > 1), Some((3, "resize images")));
+    /// ```
+    #[inline]
+    pub fn take_if<Q, F>(&self, key: &Q, pred: F) -> Option<V>
+    where
+        Q: Hash + Equivalent<K> + ?Sized,
+        F: FnOnce(&V) -> bool,
+        P: ShardSelector<Q>,
+    {
+        self.remove_if(key, |_, v| pred(v)).map(|(_, v)| v)
+    }
+
+    /// Removes the entry for `key` only if `f` returns `true` for it, returning the removed
+    /// key value pair.
+    ///
+    /// The predicate runs while the shard's write lock is held, so the entry cannot be
+    /// replaced between the check and the removal.
+    ///
+    /// **Locks** - Acquires a write lock on one of `N` shards.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use sharded::ConcurrentHashMap;
+    ///
+    /// let map = ConcurrentHashMap::new();
+    /// map.insert(1, "a");
+    /// assert!(map.remove_if(&1, |_, v| *v == "b").is_none());
+    /// assert_eq!(map.remove_if(&1, |_, v| *v == "a"), Some((1, "a")));
+    /// assert!(map.get(&1).is_none());
+    /// ```
+    #[inline]
+    pub fn remove_if<Q, F>(&self, key: &Q, f: F) -> Option<(K, V)>
+    where
+        Q: Hash + Equivalent<K> + ?Sized,
+        F: FnOnce(&K, &V) -> bool,
+        P: ShardSelector<Q>,
+    {
+        let hash = make_hash::<Q, _>(&self.hash_builder, key);
+
+        let i = self.shard_for(hash, key);
+
+        let mut shard = self.shards[i].write();
+
+        shard.remove_if(hash, key, f)
+    }
+
+    /// Atomically exchanges the values stored at `a` and `b`, returning `false` (and leaving the
+    /// map untouched) if either key is absent.
+    ///
+    /// Values are moved rather than cloned. When the keys live in different shards both shards
+    /// are locked in ascending index order, so concurrent swaps cannot deadlock.
+    ///
+    /// **Locks** - Acquires a write lock on one or two of `N` shards.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use sharded::ConcurrentHashMap;
+    ///
+    /// let map = ConcurrentHashMap::new();
+    /// map.insert("front", vec![1]);
+    /// map.insert("back", vec![2]);
+    ///
+    /// assert!(map.swap_values(&"front", &"back"));
+    /// assert_eq!(*map.get(&"front").unwrap(), vec![2]);
+    /// assert!(!map.swap_values(&"front", &"missing"));
+    /// ```
+    pub fn swap_values<Q>(&self, a: &Q, b: &Q) -> bool
+    where
+        Q: Hash + Equivalent<K> + ?Sized,
+        P: ShardSelector<Q>,
+    {
+        let hash_a = make_hash::<Q, _>(&self.hash_builder, a);
+        let hash_b = make_hash::<Q, _>(&self.hash_builder, b);
+
+        let i = self.shard_for(hash_a, a);
+        let j = self.shard_for(hash_b, b);
+
+        if i == j {
+            return self.shards[i].write().swap_values(hash_a, a, hash_b, b);
+        }
+
+        let (mut shard_a, mut shard_b) = self.write_pair(i, j);
+
+        match (shard_a.get_mut(hash_a, a), shard_b.get_mut(hash_b, b)) {
+            (Some(x), Some(y)) => {
+                std::mem::swap(x, y);
+                true
+            }
+            _ => false,
+        }
+    }
+
+    /// Moves the value stored at `old` to `new`, returning `false` (and leaving the map
+    /// untouched) if `old` is absent. Any value previously stored at `new` is dropped.
+    ///
+    /// Both shards are held for the whole move, so no reader observes the value under both keys
+    /// or under neither. Shards are locked in ascending index order, so concurrent renames cannot
+    /// deadlock.
+    ///
+    /// **Locks** - Acquires a write lock on one or two of `N` shards.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use sharded::ConcurrentHashMap;
+    ///
+    /// let map = ConcurrentHashMap::new();
+    /// map.insert("draft", 7);
+    ///
+    /// assert!(map.rename(&"draft", "published"));
+    /// assert!(map.get(&"draft").is_none());
+    /// assert_eq!(*map.get(&"published").unwrap(), 7);
+    /// assert!(!map.rename(&"draft", "published"));
+    /// ```
+    pub fn rename<Q>(&self, old: &Q, new: K) -> bool
+    where
+        Q: Hash + Equivalent<K> + ?Sized,
+        K: Hash + Eq,
+        P: ShardSelector<Q>,
+        P: ShardSelector<K>,
+    {
+        let hash_old = make_hash::<Q, _>(&self.hash_builder, old);
+        let hash_new = make_hash::<K, _>(&self.hash_builder, &new);
+
+        let i = self.shard_for(hash_old, old);
+        let j = self.shard_for(hash_new, &new);
+
+        if i == j {
+            let mut shard = self.shards[i].write();
+            return match shard.remove(hash_old, old) {
+                Some(v) => {
+                    shard.insert(hash_new, new, v, &self.hash_builder);
+                    self.notifiers[j].notify();
+                    true
+                }
+                None => false,
+            };
+        }
+
+        let (mut from, mut to) = self.write_pair(i, j);
+
+        match from.remove(hash_old, old) {
+            Some(v) => {
+                to.insert(hash_new, new, v, &self.hash_builder);
+                self.notifiers[j].notify();
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Index of the shard owning `key`, whose hash is `hash`.
+    #[inline]
+    fn shard_for<Q>(&self, hash: u64, key: &Q) -> usize
+    where
+        Q: ?Sized,
+        P: ShardSelector<Q>,
+    {
+        select_shard(&self.selector, hash, key, N)
+    }
+
+    /// Sum of shard lengths, read locking one shard at a time
+    fn total_len(&self) -> usize {
+        self.shards.iter().map(|lock| lock.read().len()).sum()
+    }
+
+    /// Write locks two distinct shards in ascending index order, returning the guards in
+    /// argument order
+    fn write_pair(
+        &self,
+        i: usize,
+        j: usize,
+    ) -> (ShardWriteGuard<'_, K, V>, ShardWriteGuard<'_, K, V>) {
+        debug_assert_ne!(i, j);
+
+        if i < j {
+            let a = self.shards[i].write();
+            (a, self.shards[j].write())
+        } else {
+            let b = self.shards[j].write();
+            (self.shards[i].write(), b)
+        }
+    }
+
+    /// Returns a clone of a uniformly random entry, or `None` if the map is empty.
+    ///
+    /// The shard is chosen with probability proportional to its length and the entry is then
+    /// sampled within it, so no iteration over the map is needed.
+    ///
+    /// **Locks** - Briefly acquires a read lock on each shard to weigh the choice, then a read
+    /// lock on the selected shard.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use sharded::ConcurrentHashMap;
+    ///
+    /// let map = ConcurrentHashMap::new();
+    /// map.insert(1, "a");
+    /// assert_eq!(map.random_entry(&mut rand::rng()), Some((1, "a")));
+    /// ```
+    #[cfg(feature = "rand")]
+    pub fn random_entry<R>(&self, rng: &mut R) -> Option<(K, V)>
+    where
+        K: Clone,
+        V: Clone,
+        R: rand::Rng + ?Sized,
+    {
+        loop {
+            let shard = self.shards[self.random_shard(rng)?].read();
+
+            // the shard may have been drained since it was weighed, try again
+            if let Some(index) = shard.random_bucket(rng) {
+                return shard.inner.get_bucket(index).cloned();
+            }
+        }
+    }
+
+    /// Returns a guarded reference to a uniformly random entry, or `None` if the map is empty.
+    ///
+    /// Like [`random_entry`](Self::random_entry) but without cloning, for eviction heuristics
+    /// that only need to inspect a candidate.
+    ///
+    /// **Locks** - Briefly acquires a read lock on each shard to weigh the choice, then holds a
+    /// read lock on the selected shard until the guard is dropped.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use sharded::ConcurrentHashMap;
+    ///
+    /// let map = ConcurrentHashMap::new();
+    /// map.insert(1, "a");
+    /// assert_eq!(*map.sample(&mut rand::rng()).unwrap(), (1, "a"));
+    /// ```
+    #[cfg(feature = "rand")]
+    pub fn sample<R>(&self, rng: &mut R) -> Option<ReadGuard<'_, (K, V)>>
+    where
+        R: rand::Rng + ?Sized,
+    {
+        loop {
+            let shard = self.shards[self.random_shard(rng)?].read();
+
+            if let Some(index) = shard.random_bucket(rng) {
+                return shard.try_map(|shard| shard.inner.get_bucket(index));
+            }
+        }
+    }
+
+    /// Returns clones of up to `n` uniformly random entries, drawn with replacement.
+    ///
+    /// Shards are weighed once and each selected shard is locked once for all of its draws.
+    /// Fewer than `n` entries are returned if shards are drained concurrently.
+    ///
+    /// **Locks** - Briefly acquires a read lock on each shard to weigh the choice, then a read
+    /// lock on each selected shard, one at a time.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use sharded::ConcurrentHashMap;
+    ///
+    /// let map = ConcurrentHashMap::from([(1, "a"), (2, "b")]);
+    /// let sample = map.sample_n(&mut rand::rng(), 5);
+    /// assert_eq!(sample.len(), 5);
+    /// assert!(sample.iter().all(|(k, _)| *k == 1 || *k == 2));
+    /// ```
+    #[cfg(feature = "rand")]
+    pub fn sample_n<R>(&self, rng: &mut R, n: usize) -> Vec<(K, V)>
+    where
+        K: Clone,
+        V: Clone,
+        R: rand::Rng + ?Sized,
+    {
+        let lens: [usize; N] = std::array::from_fn(|i| self.shards[i].read().len());
+
+        let mut draws = [0usize; N];
+        for _ in 0..n {
+            match pick_weighted(&lens, rng) {
+                Some(i) => draws[i] += 1,
+                None => return Vec::new(),
+            }
+        }
+
+        let mut sample = Vec::with_capacity(n);
+        for (lock, count) in self.shards.iter().zip(draws) {
+            if count == 0 {
+                continue;
+            }
+
+            let shard = lock.read();
+            for _ in 0..count {
+                match shard.random_bucket(rng) {
+                    Some(index) => sample.extend(shard.inner.get_bucket(index).cloned()),
+                    None => break,
+                }
+            }
+        }
+
+        sample
+    }
+
+    /// Removes and returns a uniformly random entry, or `None` if the map is empty.
+    ///
+    /// **Locks** - Briefly acquires a read lock on each shard to weigh the choice, then a write
+    /// lock on the selected shard.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use sharded::ConcurrentHashMap;
+    ///
+    /// let map = ConcurrentHashMap::new();
+    /// map.insert(1, "a");
+    /// assert_eq!(map.pop_random(&mut rand::rng()), Some((1, "a")));
+    /// assert_eq!(map.pop_random(&mut rand::rng()), None);
+    /// ```
+    #[cfg(feature = "rand")]
+    pub fn pop_random<R>(&self, rng: &mut R) -> Option<(K, V)>
+    where
+        R: rand::Rng + ?Sized,
+    {
+        loop {
+            let mut shard = self.shards[self.random_shard(rng)?].write();
+
+            if let Some(index) = shard.random_bucket(rng) {
+                return match shard.inner.get_bucket_entry(index) {
+                    Ok(entry) => Some(entry.remove().0),
+                    Err(_) => None,
+                };
+            }
+        }
+    }
+
+    /// Removes and returns an arbitrary entry, or `None` if the map is empty.
+    ///
+    /// Each call starts scanning at the shard after the previous call's, so concurrent callers
+    /// spread out over the shards instead of contending on the first non-empty one. This makes
+    /// the map usable as a pool of keyed jobs.
+    ///
+    /// **Locks** - Acquires a write lock on shards in turn, one at a time, until one is
+    /// non-empty.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use sharded::ConcurrentHashMap;
+    ///
+    /// let jobs = ConcurrentHashMap::from([(1, "build"), (2, "test")]);
+    /// let mut done = vec![jobs.pop().unwrap(), jobs.pop().unwrap()];
+    /// done.sort();
+    /// assert_eq!(done, vec![(1, "build"), (2, "test")]);
+    /// assert_eq!(jobs.pop(), None);
+    /// ```
+    pub fn pop(&self) -> Option<(K, V)> {
+        let start = self.pop_cursor.fetch_add(1, Ordering::Relaxed);
+
+        for offset in 0..N {
+            let mut shard = self.shards[start.wrapping_add(offset) % N].write();
+
+            if let Some(index) = shard.inner.iter_buckets().next() {
+                return match shard.inner.get_bucket_entry(index) {
+                    Ok(entry) => Some(entry.remove().0),
+                    Err(_) => None,
+                };
+            }
+        }
+
+        None
+    }
+
+    /// Calls `f` on every entry, shard by shard.
+    ///
+    /// Each shard is read locked only while its entries are visited, so this is not a
+    /// consistent snapshot of the whole map. `f` must not write to the map, since the current
+    /// shard is locked while it runs.
+    ///
+    /// **Locks** - Acquires a read lock on each of `N` shards, one at a time.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use sharded::ConcurrentHashMap;
+    ///
+    /// let map = ConcurrentHashMap::from([(1, 10), (2, 20)]);
+    /// let mut total = 0;
+    /// map.for_each(|_, v| total += v);
+    /// assert_eq!(total, 30);
+    /// ```
+    pub fn for_each<F>(&self, mut f: F)
+    where
+        F: FnMut(&K, &V),
+    {
+        for lock in &self.shards {
+            let shard = lock.read();
+            for (k, v) in shard.inner.iter() {
+                f(k, v);
+            }
+        }
+    }
+
+    /// Calls `f` with mutable access to every value, shard by shard.
+    ///
+    /// Each shard is write locked only while its entries are visited, so readers of other
+    /// shards are not blocked. `f` must not access the map, since the current shard is locked
+    /// while it runs.
+    ///
+    /// **Locks** - Acquires a write lock on each of `N` shards, one at a time.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use sharded::ConcurrentHashMap;
+    ///
+    /// let hits = ConcurrentHashMap::from([("a", 8), ("b", 3)]);
+    /// hits.for_each_mut(|_, count| *count /= 2);
+    /// assert_eq!(*hits.get(&"a").unwrap(), 4);
+    /// assert_eq!(*hits.get(&"b").unwrap(), 1);
+    /// ```
+    pub fn for_each_mut<F>(&self, mut f: F)
+    where
+        F: FnMut(&K, &mut V),
+    {
+        for lock in &self.shards {
+            let mut shard = lock.write();
+            for (k, v) in shard.inner.iter_mut() {
+                f(k, v);
+            }
+        }
+    }
+
+    /// Replaces every value with the result of `f`, shard by shard.
+    ///
+    /// `f` receives the current value by reference, since moving it out in place is not
+    /// possible without unsafe code; use [`for_each_mut`](Self::for_each_mut) for updates that
+    /// can work on `&mut V` directly. `f` must not access the map.
+    ///
+    /// **Locks** - Acquires a write lock on each of `N` shards, one at a time.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use sharded::ConcurrentHashMap;
+    ///
+    /// let map = ConcurrentHashMap::from([("a", String::from(" Mixed ")), ("b", "case".into())]);
+    /// map.alter_all(|_, v| v.trim().to_lowercase());
+    /// assert_eq!(*map.get(&"a").unwrap(), "mixed");
+    /// ```
+    pub fn alter_all<F>(&self, mut f: F)
+    where
+        F: FnMut(&K, &V) -> V,
+    {
+        self.for_each_mut(|k, v| *v = f(k, v));
+    }
+
+    /// Folds every entry into an accumulator, shard by shard, without collecting them.
+    ///
+    /// Each shard is read locked only while its entries are folded, so this is not a
+    /// consistent snapshot of the whole map.
+    ///
+    /// **Locks** - Acquires a read lock on each of `N` shards, one at a time.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use sharded::ConcurrentHashMap;
+    ///
+    /// let map = ConcurrentHashMap::from([("a", 1), ("bb", 2)]);
+    /// let weighted = map.fold(0, |acc, (k, v)| acc + k.len() * v);
+    /// assert_eq!(weighted, 5);
+    /// ```
+    pub fn fold<B, F>(&self, init: B, mut f: F) -> B
+    where
+        F: FnMut(B, (&K, &V)) -> B,
+    {
+        let mut acc = init;
+        for lock in &self.shards {
+            let shard = lock.read();
+            acc = shard.inner.iter().fold(acc, |acc, (k, v)| f(acc, (k, v)));
+        }
+        acc
+    }
+
+    /// Reduces the values to one by repeatedly applying `f`, or returns `None` if the map is
+    /// empty. Only the first value visited is cloned.
+    ///
+    /// **Locks** - Acquires a read lock on each of `N` shards, one at a time.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use sharded::ConcurrentHashMap;
+    ///
+    /// let map = ConcurrentHashMap::from([("a", 3), ("b", 9), ("c", 4)]);
+    /// assert_eq!(map.reduce_values(|max, v| max.max(*v)), Some(9));
+    /// ```
+    pub fn reduce_values<F>(&self, mut f: F) -> Option<V>
+    where
+        V: Clone,
+        F: FnMut(V, &V) -> V,
+    {
+        self.fold(None, |acc, (_, v)| match acc {
+            Some(acc) => Some(f(acc, v)),
+            None => Some(v.clone()),
+        })
+    }
+
+    /// Returns a clone of every entry, shard by shard in index order and sorted by key within
+    /// each shard.
+    ///
+    /// With a fixed-seed hasher such as [`DeterministicState`] the result depends only on the
+    /// contents of the map, not on insertion order or the run, which makes it suitable for
+    /// golden-file tests.
+    ///
+    /// **Locks** - Acquires a read lock on each of `N` shards in turn, one at a time.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use sharded::{ConcurrentHashMap, DeterministicState};
+    ///
+    /// let a: ConcurrentHashMap<_, _, DeterministicState> = ConcurrentHashMap::default();
+    /// let b: ConcurrentHashMap<_, _, DeterministicState> = ConcurrentHashMap::default();
+    /// for i in 0..100 {
+    ///     a.insert(i, i);
+    ///     b.insert(99 - i, 99 - i);
+    /// }
+    /// assert_eq!(a.sorted_entries(), b.sorted_entries());
+    /// ```
+    #[cfg(feature = "deterministic")]
+    pub fn sorted_entries(&self) -> Vec<(K, V)>
+    where
+        K: Ord + Clone,
+        V: Clone,
+    {
+        let mut entries = Vec::new();
+        for lock in &self.shards {
+            let shard = lock.read();
+            let start = entries.len();
+            entries.extend(shard.inner.iter().cloned());
+            entries[start..].sort_unstable_by(|(a, _), (b, _)| a.cmp(b));
+        }
+        entries
+    }
+
+    /// Picks a shard index with probability proportional to its length
+    #[cfg(feature = "rand")]
+    fn random_shard<R>(&self, rng: &mut R) -> Option<usize>
+    where
+        R: rand::Rng + ?Sized,
+    {
+        let lens: [usize; N] = std::array::from_fn(|i| self.shards[i].read().len());
+        pick_weighted(&lens, rng)
+    }
+}
+
+impl<K, V, S, const N: usize, P> Default for ConcurrentHashMap<K, V, S, N, P>
+where
+    S: Default + BuildHasher,
+    P: Default,
+{
+    /// Creates an empty `ConcurrentHashMap<K, V, S, N, P>`, with the `Default` values for the
+    /// hasher and selector and `N` shards.
+    #[inline]
+    fn default() -> ConcurrentHashMap<K, V, S, N, P> {
+        ConcurrentHashMap::with_capacity_hasher_and_selector(0, S::default(), P::default())
+    }
+}
+
+/// Formats the entries like `std`'s `HashMap`.
+///
+/// Shards are read locked one at a time, so the output is not a consistent snapshot if the
+/// map is modified concurrently.
+///
+/// # Examples
+///
+/// ```
+/// use sharded::ConcurrentHashMap;
+///
+/// let map = ConcurrentHashMap::from([(1, "a")]);
+/// assert_eq!(format!("{:?}", map), r#"{1: "a"}"#);
+/// ```
+impl<K, V, S, const N: usize, P> Debug for ConcurrentHashMap<K, V, S, N, P>
+where
+    K: Debug,
+    V: Debug,
+{
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let mut map = f.debug_map();
+        for lock in &self.shards {
+            let shard = lock.read();
+            map.entries(shard.inner.iter().map(|(k, v)| (k, v)));
+        }
+        map.finish()
+    }
+}
+
+/// Two maps are equal if they hold the same keys with equal values.
+///
+/// Shards are read locked one at a time, so the result is only meaningful if neither map is
+/// modified concurrently.
+///
+/// # Examples
+///
+/// ```
+/// use sharded::ConcurrentHashMap;
+///
+/// let a = ConcurrentHashMap::from([(1, "a"), (2, "b")]);
+/// let b = ConcurrentHashMap::from([(2, "b"), (1, "a")]);
+/// assert_eq!(a, b);
+/// ```
+impl<K, V, S, const N: usize, P> PartialEq for ConcurrentHashMap<K, V, S, N, P>
+where
+    K: Hash + Eq,
+    V: PartialEq,
+    S: BuildHasher,
+    P: ShardSelector<K>,
+{
+    fn eq(&self, other: &Self) -> bool {
+        if std::ptr::eq(self, other) {
+            return true;
+        }
+
+        // walk the map at the lower address and look up in the other, so concurrent `a == b`
+        // and `b == a` take shard locks in the same order
+        let (outer, inner) = if (self as *const Self) < (other as *const Self) {
+            (self, other)
+        } else {
+            (other, self)
+        };
+
+        outer.total_len() == inner.total_len()
+            && outer.shards.iter().all(|lock| {
+                lock.read()
+                    .inner
+                    .iter()
+                    .all(|(k, v)| inner.with(k, |w| v == w).unwrap_or(false))
+            })
+    }
+}
+
+impl<K, V, S, const N: usize, P> Eq for ConcurrentHashMap<K, V, S, N, P>
+where
+    K: Hash + Eq,
+    V: Eq,
+    S: BuildHasher,
+    P: ShardSelector<K>,
+{
+}
+
+/// A map equals a `std` `HashMap` holding the same keys with equal values.
+///
+/// # Examples
+///
+/// ```
+/// use sharded::ConcurrentHashMap;
+/// use std::collections::HashMap;
+///
+/// let map = ConcurrentHashMap::from([(1, "a")]);
+/// assert_eq!(map, HashMap::from([(1, "a")]));
+/// assert_eq!(HashMap::from([(1, "a")]), map);
+/// ```
+impl<K, V, S, S2, const N: usize, P> PartialEq<HashMap<K, V, S2>>
+    for ConcurrentHashMap<K, V, S, N, P>
+where
+    K: Hash + Eq,
+    V: PartialEq,
+    S: BuildHasher,
+    S2: BuildHasher,
+{
+    fn eq(&self, other: &HashMap<K, V, S2>) -> bool {
+        self.total_len() == other.len()
+            && self.shards.iter().all(|lock| {
+                lock.read()
+                    .inner
+                    .iter()
+                    .all(|(k, v)| other.get(k) == Some(v))
+            })
+    }
+}
+
+impl<K, V, S, S2, const N: usize, P> PartialEq<ConcurrentHashMap<K, V, S, N, P>>
+    for HashMap<K, V, S2>
+where
+    K: Hash + Eq,
+    V: PartialEq,
+    S: BuildHasher,
+    S2: BuildHasher,
+{
+    fn eq(&self, other: &ConcurrentHashMap<K, V, S, N, P>) -> bool {
+        other == self
+    }
+}
+
+/// A snapshot of a map's effective configuration, see [`ConcurrentHashMap::config`].
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+#[non_exhaustive]
+pub struct MapConfig {
+    /// Number of shards, `N`
+    pub shard_count: usize,
+    /// Type name of the hash builder, `S`
+    pub hasher: &'static str,
+    /// Type name of the shard selector, `P`
+    pub selector: &'static str,
+    /// Whether readers queue behind waiting writers
+    pub read_policy: ReadPolicy,
+    /// Approximate number of elements the map can hold without reallocating
+    pub capacity: usize,
+}
+
+/// Occupancy of one shard, see [`ConcurrentHashMap::shard_stats`].
+#[derive(Clone, Copy, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+#[non_exhaustive]
+pub struct ShardStats {
+    /// Number of entries in the shard
+    pub len: usize,
+    /// Number of entries the shard can hold without reallocating
+    pub capacity: usize,
+    /// `len / capacity`, or 0 for an unallocated shard
+    pub load_factor: f64,
+}
+
+/// Inserts all pairs through a shared reference, since the map only needs `&self` to insert.
+/// Pairs are grouped by shard as in [`insert_batch`](ConcurrentHashMap::insert_batch).
+///
+/// # Examples
+///
+/// ```
+/// use sharded::ConcurrentHashMap;
+///
+/// let map = ConcurrentHashMap::new();
+/// (&map).extend([(1, "a"), (2, "b")]);
+/// assert_eq!(*map.get(&2).unwrap(), "b");
+/// ```
+impl<K, V, S, const N: usize, P> Extend<(K, V)> for &ConcurrentHashMap<K, V, S, N, P>
+where
+    K: Hash + Eq,
+    S: BuildHasher,
+    P: ShardSelector<K>,
+{
+    #[inline]
+    fn extend<T: IntoIterator<Item = (K, V)>>(&mut self, iter: T) {
+        self.insert_batch(iter)
+    }
+}
+
+/// Inserts all pairs, grouped by shard as in [`insert_batch`](ConcurrentHashMap::insert_batch).
+///
+/// # Examples
+///
+/// ```
+/// use sharded::ConcurrentHashMap;
+///
+/// let mut map = ConcurrentHashMap::new();
+/// map.extend((0..100).map(|i| (i, i)));
+/// assert_eq!(*map.get(&42).unwrap(), 42);
+/// ```
+impl<K, V, S, const N: usize, P> Extend<(K, V)> for ConcurrentHashMap<K, V, S, N, P>
+where
+    K: Hash + Eq,
+    S: BuildHasher,
+    P: ShardSelector<K>,
+{
+    #[inline]
+    fn extend<T: IntoIterator<Item = (K, V)>>(&mut self, iter: T) {
+        self.insert_batch(iter)
+    }
+}
+
+/// The error returned by [`try_insert`](ConcurrentHashMap::try_insert) when the key already
+/// exists.
+///
+/// Contains the value that was not inserted and a guard for the existing value. The shard stays
+/// write locked until the error is dropped.
+pub struct OccupiedError<'a, V> {
+    /// A guard for the value already present in the map.
+    pub existing: WriteGuard<'a, V>,
+    /// The value which was not inserted, because the entry was already occupied.
+    pub value: V,
+}
+
+impl<V: Debug> Debug for OccupiedError<'_, V> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("OccupiedError")
+            .field("old_value", &*self.existing)
+            .field("new_value", &self.value)
+            .finish()
+    }
+}
+
+impl<V: Debug> fmt::Display for OccupiedError<'_, V> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "failed to insert {:?}, key already exists with value {:?}",
+            self.value, &*self.existing,
+        )
+    }
+}
+
+impl<V: Debug> std::error::Error for OccupiedError<'_, V> {}
+
+/// Builds a map from an iterator of pairs, sizing the shards from the iterator's lower size
+/// hint and inserting as in [`insert_batch`](ConcurrentHashMap::insert_batch).
+///
+/// # Examples
+///
+/// ```
+/// use sharded::ConcurrentHashMap;
+///
+/// let map: ConcurrentHashMap<_, _> = (0..10).map(|i| (i, i * i)).collect();
+/// assert_eq!(*map.get(&3).unwrap(), 9);
+/// ```
+impl<K, V, S, const N: usize, P> FromIterator<(K, V)> for ConcurrentHashMap<K, V, S, N, P>
+where
+    K: Hash + Eq,
+    S: Default + BuildHasher,
+    P: ShardSelector<K> + Default,
+{
+    fn from_iter<T: IntoIterator<Item = (K, V)>>(iter: T) -> Self {
+        let iter = iter.into_iter();
+        let map = ConcurrentHashMap::with_capacity_hasher_and_selector(
+            iter.size_hint().0,
+            S::default(),
+            P::default(),
+        );
+        map.insert_batch(iter);
+        map
+    }
+}
+
+/// Builds a map with the default hasher and shard count from an array of pairs. Use
+/// [`collect`](Iterator::collect) for other configurations.
+///
+/// # Examples
+///
+/// ```
+/// use sharded::ConcurrentHashMap;
+///
+/// let map = ConcurrentHashMap::from([("a", 1), ("b", 2)]);
+/// assert_eq!(*map.get(&"b").unwrap(), 2);
+/// ```
+impl<K, V, const M: usize> From<[(K, V); M]>
+    for ConcurrentHashMap<K, V, RandomState, DEFAULT_SHARD_COUNT>
+where
+    K: Hash + Eq,
+{
+    fn from(arr: [(K, V); M]) -> Self {
+        arr.into_iter().collect()
+    }
+}
+
+/// Distributes the entries of a `std` `HashMap` over the shards, keeping its hasher.
+///
+/// # Examples
+///
+/// ```
+/// use sharded::ConcurrentHashMap;
+/// use std::collections::HashMap;
+///
+/// let map: ConcurrentHashMap<_, _> = HashMap::from([(1, "a"), (2, "b")]).into();
+/// assert_eq!(*map.get(&2).unwrap(), "b");
+/// ```
+impl<K, V, S, const N: usize, P> From<HashMap<K, V, S>> for ConcurrentHashMap<K, V, S, N, P>
+where
+    K: Hash + Eq,
+    S: BuildHasher + Clone,
+    P: ShardSelector<K> + Default,
+{
+    fn from(map: HashMap<K, V, S>) -> Self {
+        let out = ConcurrentHashMap::with_capacity_hasher_and_selector(
+            map.len(),
+            map.hasher().clone(),
+            P::default(),
+        );
+        out.insert_batch(map);
+        out
+    }
+}
+
+impl<K, V, S, const N: usize, P> ConcurrentHashMap<K, V, S, N, P>
+where
+    K: Hash + Eq + Send,
+    V: Send,
+    S: BuildHasher + Clone,
+    P: ShardSelector<K> + Default,
+    Self: Sync,
+{
+    /// Like the `From<HashMap>` conversion, but hashes and inserts the entries on `threads`
+    /// scoped threads.
+    ///
+    /// **Locks** - Each thread acquires a write lock on each shard receiving its entries, one
+    /// at a time.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use sharded::ConcurrentHashMap;
+    /// use std::collections::HashMap;
+    ///
+    /// let source: HashMap<_, _> = (0..10_000).map(|i| (i, i)).collect();
+    /// let map: ConcurrentHashMap<_, _> = ConcurrentHashMap::from_hash_map_parallel(source, 4);
+    /// assert_eq!(*map.get(&1234).unwrap(), 1234);
+    /// ```
+    pub fn from_hash_map_parallel(map: HashMap<K, V, S>, threads: usize) -> Self {
+        let out = ConcurrentHashMap::with_capacity_hasher_and_selector(
+            map.len(),
+            map.hasher().clone(),
+            P::default(),
+        );
+
+        let mut entries: Vec<(K, V)> = map.into_iter().collect();
+        let chunk = entries.len().div_ceil(threads.max(1)).max(1);
+
+        std::thread::scope(|scope| {
+            while !entries.is_empty() {
+                let rest = entries.split_off(entries.len().saturating_sub(chunk));
+                let out = &out;
+                scope.spawn(move || out.insert_batch(rest));
+            }
+        });
+
+        out
+    }
+}
+
+/// Consumes the map, yielding its pairs shard by shard.
+impl<K, V, S, const N: usize, P> IntoIterator for ConcurrentHashMap<K, V, S, N, P> {
+    type Item = (K, V);
+    type IntoIter = IntoIter<K, V>;
+
+    fn into_iter(self) -> IntoIter<K, V> {
+        let shards: Vec<Shard<K, V>> = self
+            .shards
+            .into_iter()
+            .map(|lock| lock.into_inner().into_inner())
+            .collect();
+        IntoIter {
+            iter: HashTable::new().into_iter(),
+            remaining: shards.iter().map(|shard| shard.inner.len()).sum(),
+            shards,
+        }
+    }
+}
+
+impl<K, V, S, const N: usize, P> ConcurrentHashMap<K, V, S, N, P> {
+    /// Consumes the map, yielding its values.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use sharded::ConcurrentHashMap;
+    ///
+    /// let map = ConcurrentHashMap::from([("a", 1), ("b", 2)]);
+    /// assert_eq!(map.into_values().sum::<i32>(), 3);
+    /// ```
+    pub fn into_values(self) -> IntoValues<K, V> {
+        IntoValues {
+            iter: self.into_iter(),
+        }
+    }
+
+    /// Unwraps the shard locks and merges all shards into a single `std` `HashMap` using this
+    /// map's hasher, e.g. for a single-threaded read phase after a parallel build.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use sharded::ConcurrentHashMap;
+    ///
+    /// let map: ConcurrentHashMap<_, _> = (0..100).map(|i| (i, i * 2)).collect();
+    /// let plain = map.into_inner();
+    /// assert_eq!(plain.len(), 100);
+    /// assert_eq!(plain[&21], 42);
+    /// ```
+    pub fn into_inner(self) -> HashMap<K, V, S>
+    where
+        K: Hash + Eq,
+        S: BuildHasher,
+    {
+        let ConcurrentHashMap {
+            hash_builder,
+            shards,
+            ..
+        } = self;
+
+        let shards = shards.map(|lock| lock.into_inner().into_inner());
+        let len = shards.iter().map(|shard| shard.inner.len()).sum();
+
+        let mut map = HashMap::with_capacity_and_hasher(len, hash_builder);
+        for shard in shards {
+            map.extend(shard.inner);
+        }
+        map
+    }
+
+    /// Consumes the map and redistributes its entries under `hash_builder`, which may be of a
+    /// different type, e.g. after [`is_skewed`](Self::is_skewed) shows the current hasher
+    /// piling keys into a few shards.
+    ///
+    /// Taking the map by value guarantees nobody observes it half migrated. To migrate while
+    /// readers keep using the old map, build the new one and [`drain_into`](Self::drain_into)
+    /// it instead.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use sharded::ConcurrentHashMap;
+    /// use std::collections::hash_map::RandomState;
+    ///
+    /// let map: ConcurrentHashMap<_, _> = (0..100).map(|i| (i, i)).collect();
+    /// let map = map.rebalance_with_hasher(RandomState::new());
+    /// assert_eq!(*map.get(&42).unwrap(), 42);
+    /// ```
+    pub fn rebalance_with_hasher<S2>(self, hash_builder: S2) -> ConcurrentHashMap<K, V, S2, N, P>
+    where
+        K: Hash + Eq,
+        S2: BuildHasher,
+        P: ShardSelector<K>,
+    {
+        let policy = self.shards[0].policy();
+        let ConcurrentHashMap {
+            selector, shards, ..
+        } = self;
+
+        let shards = shards.map(|lock| lock.into_inner().into_inner());
+        let len = shards.iter().map(|shard| shard.inner.len()).sum();
+
+        let mut map =
+            ConcurrentHashMap::with_capacity_hasher_and_selector(len, hash_builder, selector);
+        map.set_read_policy(policy);
+        for shard in shards {
+            map.insert_batch(shard.inner);
+        }
+        map
+    }
+}
+
+/// An owning iterator over the entries of a `ConcurrentHashMap`.
+///
+/// This `struct` is created by the [`into_iter`] method on [`ConcurrentHashMap`]
+/// (provided by the [`IntoIterator`] trait). See its documentation for more.
+///
+/// [`into_iter`]: IntoIterator::into_iter
+///
+/// # Example
+///
+/// ```
+/// use sharded::ConcurrentHashMap;
+///
+/// let map = ConcurrentHashMap::from([
+///     ("a", 1),
+/// ]);
+/// let mut iter = map.into_iter();
+/// assert_eq!(iter.next(), Some(("a", 1)));
+/// ```
+pub struct IntoIter<K, V> {
+    iter: hash_table::IntoIter<(K, V)>,
+    shards: Vec<Shard<K, V>>,
+    // items left across `iter` and `shards`
+    remaining: usize,
+}
+
+/// An owning iterator over the values of a `ConcurrentHashMap`.
+///
+/// This `struct` is created by [`ConcurrentHashMap::into_values`].
+pub struct IntoValues<K, V> {
+    iter: IntoIter<K, V>,
+}
+
+impl<K, V> Iterator for IntoIter<K, V> {
+    type Item = (K, V);
+
+    #[inline]
+    fn next(&mut self) -> Option<(K, V)> {
+        match self.iter.next() {
+            Some(item) => {
+                self.remaining -= 1;
+                Some(item)
+            }
+            None => match self.shards.pop() {
+                Some(s) => {
+                    self.iter = s.inner.into_iter();
+                    self.next()
+                }
+                None => None,
+            },
+        }
+    }
+
+    #[inline]
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        (self.remaining, Some(self.remaining))
+    }
+}
+
+impl<K, V> ExactSizeIterator for IntoIter<K, V> {}
+
+impl<K, V> Iterator for IntoValues<K, V> {
+    type Item = V;
+
+    #[inline]
+    fn next(&mut self) -> Option<V> {
+        self.iter.next().map(|(_, v)| v)
+    }
+
+    #[inline]
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        self.iter.size_hint()
+    }
+}
+
+impl<K, V> ExactSizeIterator for IntoValues<K, V> {}
+
+/// Read guard over a whole shard
+type ShardReadGuard<'a, K, V> = Locked<'a, RwLockReadGuard<'a, Shard<K, V>>>;
+
+/// Write guard over a whole shard
+type ShardWriteGuard<'a, K, V> = Locked<'a, RwLockWriteGuard<'a, Shard<K, V>>>;
+
+/// Atomically downgrades `shard` to a read lock over the value in bucket `index`, which the
+/// caller just filled under the write lock
+fn downgrade_to_bucket<K, V>(shard: ShardWriteGuard<'_, K, V>, index: usize) -> ReadGuard<'_, V> {
+    match shard
+        .downgrade()
+        .try_map(|shard| shard.inner.get_bucket(index).map(|(_, v)| v))
+    {
+        Some(value) => value,
+        // no writer can get in between the insert and the downgrade, so the bucket is full
+        None => unreachable!("bucket {index} was emptied while the shard was locked"),
+    }
+}
+
+/// A single shard in the map. Methods that may grow the table take the map's hash builder,
+/// so it isn't cloned into every shard.
+#[derive(Clone)]
+pub(crate) struct Shard<K, V> {
+    inner: HashTable<(K, V)>,
+}
+
+impl<K, V> Debug for Shard<K, V>
+where
+    K: Debug,
+    V: Debug,
+{
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("shard").finish()
+    }
+}
+
+#[allow(dead_code)]
+impl<K, V> Shard<K, V> {
+    /// Number of items in the shard
+    #[inline]
+    pub(crate) fn len(&self) -> usize {
+        self.inner.len()
+    }
+
+    /// Is `len == 0`
+    #[inline]
+    pub(crate) fn is_empty(&self) -> bool {
+        self.inner.len() == 0
+    }
+
+    /// Reserve room for at least `additional` more items
+    #[inline]
+    pub(crate) fn reserve<S>(&mut self, additional: usize, hash_builder: &S)
+    where
+        K: Hash,
+        S: BuildHasher,
+    {
+        let hasher = make_hasher::<K, V, S>(hash_builder);
+        self.inner.reserve(additional, hasher)
+    }
+
+    /// Try to reserve room for at least `additional` more items
+    #[inline]
+    pub(crate) fn try_reserve<S>(
+        &mut self,
+        additional: usize,
+        hash_builder: &S,
+    ) -> Result<(), TryReserveError>
+    where
+        K: Hash,
+        S: BuildHasher,
+    {
+        let hasher = make_hasher::<K, V, S>(hash_builder);
+        self.inner.try_reserve(additional, hasher)
+    }
+
+    /// Remove the key, returning the value at that position if it existed
+    #[inline]
+    pub(crate) fn remove<Q>(&mut self, hash: u64, key: &Q) -> Option<V>
+    where
+        Q: Equivalent<K> + ?Sized,
+    {
+        #[allow(clippy::manual_map)] // reduce compiler IR, I think!
+        match self.inner.find_entry(hash, equivalent_key(key)) {
+            Ok(entry) => Some(entry.remove().0 .1),
+            Err(_) => None,
+        }
+    }
+
+    /// Remove the key if the predicate holds for its entry
+    #[inline]
+    pub(crate) fn remove_if<Q, F>(&mut self, hash: u64, key: &Q, f: F) -> Option<(K, V)>
+    where
+        Q: Equivalent<K> + ?Sized,
+        F: FnOnce(&K, &V) -> bool,
+    {
+        match self.inner.find_entry(hash, equivalent_key(key)) {
+            Ok(entry) if f(&entry.get().0, &entry.get().1) => Some(entry.remove().0),
+            _ => None,
+        }
+    }
+
+    /// Index of a uniformly random occupied bucket
+    #[cfg(feature = "rand")]
+    pub(crate) fn random_bucket<R>(&self, rng: &mut R) -> Option<usize>
+    where
+        R: rand::Rng + ?Sized,
+    {
+        let len = self.inner.len();
+        if len == 0 {
+            return None;
+        }
+
+        // rejection sampling is cheap while the table is reasonably full, fall back to
+        // walking the occupied buckets when it's sparse (e.g. after many removals)
+        let buckets = self.inner.num_buckets();
+        if len * 8 >= buckets {
+            loop {
+                let index = rng.random_range(0..buckets);
+                if self.inner.get_bucket(index).is_some() {
+                    return Some(index);
+                }
+            }
+        }
+
+        self.inner.iter_buckets().nth(rng.random_range(0..len))
+    }
+
+    /// Exchange the values of two keys in this shard, `false` if either is absent
+    #[inline]
+    pub(crate) fn swap_values<Q>(&mut self, hash_a: u64, a: &Q, hash_b: u64, b: &Q) -> bool
+    where
+        Q: Equivalent<K> + ?Sized,
+    {
+        let index_a = self.inner.find_bucket_index(hash_a, equivalent_key(a));
+        let index_b = self.inner.find_bucket_index(hash_b, equivalent_key(b));
+
+        match (index_a, index_b) {
+            (Some(x), Some(y)) if x == y => return true,
+            (Some(_), Some(_)) => (),
+            _ => return false,
+        }
+
+        let keys = [a, b];
+        match self
+            .inner
+            .get_disjoint_mut([hash_a, hash_b], |i, (k, _)| keys[i].equivalent(k))
+        {
+            [Some(x), Some(y)] => {
+                std::mem::swap(&mut x.1, &mut y.1);
+                true
+            }
+            _ => false,
+        }
+    }
+
+    /// Get mutable value for the provided key
+    #[inline]
+    pub(crate) fn get_mut<Q>(&mut self, hash: u64, key: &Q) -> Option<&mut V>
+    where
+        Q: Equivalent<K> + ?Sized,
+    {
+        match self.inner.find_mut(hash, equivalent_key(key)) {
+            Some(&mut (_, ref mut v)) => Some(v),
+            None => None,
+        }
+    }
+
+    /// Insert the key value pair
+    #[inline]
+    pub(crate) fn insert<S>(&mut self, hash: u64, key: K, v: V, hash_builder: &S) -> Option<V>
+    where
+        K: Hash + Eq,
+        S: BuildHasher,
+    {
+        match self.inner.entry(
+            hash,
+            equivalent_key(&key),
+            make_hasher::<K, V, S>(hash_builder),
+        ) {
+            Entry::Occupied(mut entry) => Some(std::mem::replace(&mut entry.get_mut().1, v)),
+            Entry::Vacant(entry) => {
+                entry.insert((key, v));
+                None
+            }
+        }
+    }
+
+    /// Get the value for the key, inserting the result of `f` if it is absent
+    #[inline]
+    pub(crate) fn get_or_insert_with<F, S>(
+        &mut self,
+        hash: u64,
+        key: K,
+        f: F,
+        hash_builder: &S,
+    ) -> &mut V
+    where
+        K: Hash + Eq,
+        F: FnOnce() -> V,
+        S: BuildHasher,
+    {
+        let entry = self.inner.entry(
+            hash,
+            equivalent_key(&key),
+            make_hasher::<K, V, S>(hash_builder),
+        );
+
+        &mut entry.or_insert_with(|| (key, f())).into_mut().1
+    }
+
+    /// Bucket index of the key, inserting the result of `f` if it is absent and `f` succeeds
+    #[inline]
+    pub(crate) fn try_get_or_insert_with<F, E, S>(
+        &mut self,
+        hash: u64,
+        key: K,
+        f: F,
+        hash_builder: &S,
+    ) -> Result<usize, E>
+    where
+        K: Hash + Eq,
+        F: FnOnce() -> Result<V, E>,
+        S: BuildHasher,
+    {
+        match self.inner.entry(
+            hash,
+            equivalent_key(&key),
+            make_hasher::<K, V, S>(hash_builder),
+        ) {
+            Entry::Occupied(entry) => Ok(entry.bucket_index()),
+            Entry::Vacant(entry) => Ok(entry.insert((key, f()?)).bucket_index()),
+        }
+    }
+
+    /// Insert a key that is known to be absent, returning its value
+    #[inline]
+    pub(crate) fn insert_unique<S>(&mut self, hash: u64, key: K, v: V, hash_builder: &S) -> &mut V
+    where
+        K: Hash,
+        S: BuildHasher,
+    {
+        let hasher = make_hasher::<K, V, S>(hash_builder);
+        &mut self
+            .inner
+            .insert_unique(hash, (key, v), hasher)
+            .into_mut()
+            .1
+    }
+
+    /// Get the value for the key if it exists
+    #[inline]
+    pub(crate) fn get<Q>(&self, hash: u64, key: &Q) -> Option<&V>
+    where
+        Q: Equivalent<K> + ?Sized,
+    {
+        match self.inner.find(hash, equivalent_key(key)) {
+            Some((_, v)) => Some(v),
+            None => None,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::Arc;
+    use std::time::Duration;
+
+    #[test]
+    fn test_apprx_capacity() {
+        let _map: ConcurrentHashMap<usize, usize, RandomState, 120> =
+            ConcurrentHashMap::with_capacity_and_hasher(1000, RandomState::new());
+    }
+
+    #[test]
+    fn test_insert_values() {
+        let map = ConcurrentHashMap::new();
+        {
+            map.insert("k", "v");
+        }
+        assert_eq!(*map.get(&"k").unwrap(), "v");
+    }
+
+    #[test]
+    fn test_remove_if() {
+        let map = ConcurrentHashMap::new();
+        map.insert("k", 1);
+        assert_eq!(map.remove_if(&"k", |_, v| *v > 1), None);
+        assert_eq!(*map.get(&"k").unwrap(), 1);
+        assert_eq!(map.remove_if(&"k", |_, v| *v == 1), Some(("k", 1)));
+        assert!(map.get(&"k").is_none());
+        assert_eq!(map.remove_if(&"k", |_, _| true), None);
+    }
+
+    #[cfg(feature = "rand")]
+    #[test]
+    fn test_random_entry() {
+        use rand::SeedableRng;
+
+        let mut rng = rand::rngs::StdRng::seed_from_u64(7);
+        let map = ConcurrentHashMap::new();
+        assert_eq!(map.random_entry(&mut rng), None);
+
+        for i in 0..1000 {
+            map.insert(i, i * 2);
+        }
+
+        for _ in 0..100 {
+            let (k, v) = map.random_entry(&mut rng).unwrap();
+            assert_eq!(v, k * 2);
+        }
+
+        let mut popped = std::collections::HashSet::new();
+        while let Some((k, v)) = map.pop_random(&mut rng) {
+            assert_eq!(v, k * 2);
+            assert!(popped.insert(k));
+        }
+        assert_eq!(popped.len(), 1000);
+    }
+
+    #[test]
+    fn test_try_insert() {
+        let map = ConcurrentHashMap::new();
+        *map.try_insert("k", 1).unwrap() += 1;
+
+        let err = map.try_insert("k", 10).unwrap_err();
+        assert_eq!(*err.existing, 2);
+        assert_eq!(err.value, 10);
+        assert_eq!(
+            err.to_string(),
+            "failed to insert 10, key already exists with value 2"
+        );
+        drop(err);
+
+        assert_eq!(*map.get(&"k").unwrap(), 2);
+    }
+
+    #[test]
+    fn test_swap_values() {
+        let map: ConcurrentHashMap<i32, String, RandomState, 2> = ConcurrentHashMap::default();
+        for i in 0..8 {
+            map.insert(i, i.to_string());
+        }
+
+        // covers keys in the same shard as well as across both shards
+        for i in 1..8 {
+            assert!(map.swap_values(&0, &i));
+            assert_eq!(*map.get(&0).unwrap(), i.to_string());
+            assert!(map.swap_values(&i, &0));
+            assert_eq!(*map.get(&i).unwrap(), i.to_string());
+        }
+
+        assert!(map.swap_values(&3, &3));
+        assert!(!map.swap_values(&3, &100));
+        assert_eq!(*map.get(&3).unwrap(), "3");
+    }
+
+    #[test]
+    fn test_get_key_value() {
+        #[derive(Debug)]
+        struct User {
+            id: u32,
+            name: &'static str,
+        }
+
+        impl PartialEq for User {
+            fn eq(&self, other: &Self) -> bool {
+                self.id == other.id
+            }
+        }
+
+        impl Eq for User {}
+
+        impl Hash for User {
+            fn hash<H: std::hash::Hasher>(&self, state: &mut H) {
+                self.id.hash(state);
+            }
+        }
+
+        let map = ConcurrentHashMap::new();
+        map.insert(
+            User {
+                id: 1,
+                name: "Henry",
+            },
+            10,
+        );
+
+        let probe = User { id: 1, name: "" };
+        let entry = map.get_key_value(&probe).unwrap();
+        assert_eq!(entry.0.name, "Henry");
+        assert_eq!(entry.1, 10);
+    }
+
+    #[test]
+    fn test_borrowed_key_lookups() {
+        let map = ConcurrentHashMap::new();
+        map.insert(String::from("a"), 1);
+        map.insert(String::from("b"), 2);
+
+        assert_eq!(*map.get("a").unwrap(), 1);
+        assert!(map.contains_key("b"));
+        *map.get_mut("b").unwrap() *= 10;
+        assert_eq!(*map.get_key_value("b").unwrap(), (String::from("b"), 20));
+        assert!(map.swap_values("a", "b"));
+        assert_eq!(
+            map.remove_if("a", |_, v| *v == 20),
+            Some((String::from("a"), 20))
+        );
+        assert!(!map.contains_key("a"));
+    }
+
+    #[test]
+    fn test_extend_shared() {
+        let map = ConcurrentHashMap::new();
+        let chunks = vec![vec![(1, 1), (2, 2)], vec![(2, 20), (3, 3)]];
+        chunks.into_iter().for_each(|chunk| (&map).extend(chunk));
+
+        assert_eq!(*map.get(&1).unwrap(), 1);
+        assert_eq!(*map.get(&2).unwrap(), 20);
+        assert_eq!(*map.get(&3).unwrap(), 3);
+    }
+
+    #[test]
+    fn test_guard_outlives_key() {
+        let map = ConcurrentHashMap::new();
+        map.insert(String::from("k"), 1);
+
+        let guard = map.get(String::from("k").as_str()).unwrap();
+        assert_eq!(*guard, 1);
+        drop(guard);
+
+        let pair = map.get_key_value(&String::from("k")).unwrap();
+        assert_eq!(pair.1, 1);
+    }
+
+    #[test]
+    fn test_equivalent_lookups() {
+        #[derive(Hash)]
+        struct Probe<'a>(u64, &'a str);
+
+        impl Equivalent<(u64, String)> for Probe<'_> {
+            fn equivalent(&self, key: &(u64, String)) -> bool {
+                self.0 == key.0 && self.1 == key.1
+            }
+        }
+
+        let map = ConcurrentHashMap::new();
+        map.insert((1, String::from("a")), "first");
+        map.insert((2, String::from("b")), "second");
+
+        assert_eq!(*map.get(&Probe(1, "a")).unwrap(), "first");
+        assert!(!map.contains_key(&Probe(1, "b")));
+        assert!(map.swap_values(&Probe(1, "a"), &Probe(2, "b")));
+        assert!(map.swap_values(&Probe(1, "a"), &Probe(1, "a")));
+        assert_eq!(*map.get(&Probe(2, "b")).unwrap(), "first");
+    }
+
+    #[test]
+    fn test_remove() {
+        let map = ConcurrentHashMap::new();
+        map.insert(String::from("a"), 1);
+        map.insert(String::from("b"), 2);
+
+        assert_eq!(map.remove("a"), Some(1));
+        assert_eq!(map.remove("a"), None);
+        assert!(!map.contains_key("a"));
+        assert_eq!(*map.get("b").unwrap(), 2);
+    }
+
+    #[test]
+    fn test_push_and_take_values() {
+        let map: ConcurrentHashMap<u32, Vec<u32>> = ConcurrentHashMap::new();
+        for i in 0..100 {
+            map.push_value(i % 3, i);
+        }
+
+        assert_eq!(map.take_values(&0).len(), 34);
+        assert!(map.take_values(&0).is_empty());
+        assert_eq!(map.take_values(&2).iter().sum::<u32>(), 1650);
+    }
+
+    #[test]
+    fn test_get_many() {
+        let map: ConcurrentHashMap<i32, i32, RandomState, 4> = ConcurrentHashMap::default();
+        for i in 0..32 {
+            map.insert(i, i * 10);
+        }
+
+        let keys: [&i32; 8] = [&7, &3, &40, &3, &31, &0, &16, &9];
+        let values = map.get_many(keys);
+        for (key, value) in keys.iter().zip(values.iter()) {
+            match value {
+                Some(v) => assert_eq!(**v, **key * 10),
+                None => assert_eq!(**key, 40),
+            }
+        }
+    }
+
+    #[test]
+    fn test_rename() {
+        let map: ConcurrentHashMap<u32, &str, RandomState, 2> = ConcurrentHashMap::default();
+        for i in 0..4 {
+            map.insert(i, "v");
+        }
+        map.insert(10, "moved");
+
+        // crosses shards for some pairs and stays in one for others
+        assert!(map.rename(&10, 11));
+        assert!(map.rename(&11, 12));
+        assert!(map.rename(&12, 0));
+        assert_eq!(*map.get(&0).unwrap(), "moved");
+        assert!(map.get(&10).is_none() && map.get(&11).is_none() && map.get(&12).is_none());
+        assert!(map.rename(&0, 0));
+        assert!(!map.rename(&42, 1));
+        assert_eq!(*map.get(&1).unwrap(), "v");
+    }
+
+    #[test]
+    fn test_insert_batch() {
+        let map: ConcurrentHashMap<u32, u32, RandomState, 4> = ConcurrentHashMap::default();
+        map.insert(7, 0);
+        map.insert_batch((0..100).map(|i| (i, i + 1)));
+        map.insert_batch(std::iter::empty());
+
+        assert_eq!(
+            map.shards.iter().map(|s| s.read().len()).sum::<usize>(),
+            100
+        );
+        assert_eq!(*map.get(&7).unwrap(), 8);
+        assert_eq!(*map.get(&99).unwrap(), 100);
+    }
+
+    #[test]
+    fn test_remove_batch() {
+        let map: ConcurrentHashMap<u32, u32, RandomState, 4> = ConcurrentHashMap::default();
+        map.insert_batch((0..100).map(|i| (i, i)));
+
+        let keys: Vec<u32> = (0..100).step_by(2).chain([0, 500]).collect();
+        let refs: Vec<&u32> = keys.iter().collect();
+        let removed = map.remove_batch(&refs);
+
+        assert_eq!(removed.len(), 52);
+        assert_eq!(removed[..50].iter().flatten().count(), 50);
+        assert_eq!(removed[50..], [None, None]);
+        assert_eq!(map.shards.iter().map(|s| s.read().len()).sum::<usize>(), 50);
+    }
+
+    #[test]
+    fn test_collect_and_into_iter() {
+        let map: ConcurrentHashMap<u32, u32, RandomState, 4> = (0..100).map(|i| (i, i)).collect();
+        assert!(map.capacity() >= 100);
+
+        let mut pairs: Vec<_> = map.into_iter().collect();
+        pairs.sort_unstable();
+        assert_eq!(pairs, (0..100).map(|i| (i, i)).collect::<Vec<_>>());
+    }
+
+    #[test]
+    fn test_partial_eq() {
+        let a: ConcurrentHashMap<u32, u32, RandomState, 4> = (0..10).map(|i| (i, i)).collect();
+        let b: ConcurrentHashMap<u32, u32, RandomState, 4> =
+            (0..10).rev().map(|i| (i, i)).collect();
+        assert!(a == a);
+        assert_eq!(a, b);
+        assert_eq!(b, a);
+
+        b.insert(3, 0);
+        assert_ne!(a, b);
+        b.insert(3, 3);
+        b.insert(10, 10);
+        assert_ne!(a, b);
+
+        let std: HashMap<u32, u32> = (0..10).map(|i| (i, i)).collect();
+        assert_eq!(a, std);
+        assert_eq!(std, a);
+        assert_ne!(b, std);
+    }
+
+    #[test]
+    fn test_into_iter_exact_size() {
+        let map: ConcurrentHashMap<u32, u32, RandomState, 4> = (0..10).map(|i| (i, i)).collect();
+        let mut iter = map.into_iter();
+        assert_eq!(iter.len(), 10);
+        iter.nth(3);
+        assert_eq!(iter.size_hint(), (6, Some(6)));
+        assert_eq!(iter.by_ref().count(), 6);
+        assert_eq!(iter.len(), 0);
+
+        let map: ConcurrentHashMap<u32, u32, RandomState, 4> = (0..10).map(|i| (i, i)).collect();
+        assert_eq!(map.into_values().len(), 10);
+    }
+
+    #[test]
+    fn test_capacity_sums_uneven_shards() {
+        let map: ConcurrentHashMap<u32, u32, RandomState, 4> = ConcurrentHashMap::default();
+        map.shards[2].write().reserve(100, map.hasher());
+
+        assert_eq!(map.capacity_per_shard()[0], 0);
+        assert!(map.capacity_per_shard()[2] >= 100);
+        assert_eq!(map.capacity(), map.capacity_per_shard()[2]);
+    }
+
+    #[test]
+    fn test_for_each_visits_every_entry() {
+        let map: ConcurrentHashMap<u32, u32, RandomState, 4> = (0..100).map(|i| (i, i)).collect();
+        let mut seen = Vec::new();
+        map.for_each(|k, v| {
+            assert_eq!(k, v);
+            seen.push(*k);
+        });
+        seen.sort_unstable();
+        assert_eq!(seen, (0..100).collect::<Vec<_>>());
+    }
+
+    #[test]
+    fn test_fold_and_reduce_values() {
+        let map: ConcurrentHashMap<u32, u64, RandomState, 4> = ConcurrentHashMap::default();
+        assert_eq!(map.fold(0, |acc, _| acc + 1), 0);
+        assert_eq!(map.reduce_values(|a, b| a + b), None);
+
+        map.insert_batch((1..=100).map(|i| (i, i as u64)));
+        assert_eq!(map.fold(0, |acc, (_, v)| acc + v), 5050);
+        assert_eq!(map.reduce_values(|a, b| a + b), Some(5050));
+    }
+
+    #[cfg(feature = "rand")]
+    #[test]
+    fn test_sample_n() {
+        let map: ConcurrentHashMap<u32, u32, RandomState, 4> = ConcurrentHashMap::default();
+        assert!(map.sample_n(&mut rand::rng(), 10).is_empty());
+        assert!(map.sample(&mut rand::rng()).is_none());
+
+        map.insert_batch((0..100).map(|i| (i, i)));
+        let sample = map.sample_n(&mut rand::rng(), 1000);
+        assert_eq!(sample.len(), 1000);
+        assert!(sample.iter().all(|(k, v)| k == v && *k < 100));
+
+        // 1000 draws over 100 keys should hit far more than a handful of them
+        let mut keys: Vec<_> = sample.into_iter().map(|(k, _)| k).collect();
+        keys.sort_unstable();
+        keys.dedup();
+        assert!(keys.len() > 50);
+    }
+
+    #[test]
+    fn test_pop_drains_concurrently() {
+        let map: Arc<ConcurrentHashMap<u32, u32, RandomState, 8>> =
+            Arc::new((0..1000).map(|i| (i, i)).collect());
+
+        let workers: Vec<_> = (0..4)
+            .map(|_| {
+                let map = map.clone();
+                std::thread::spawn(move || {
+                    let mut popped = Vec::new();
+                    while let Some((k, _)) = map.pop() {
+                        popped.push(k);
+                    }
+                    popped
+                })
+            })
+            .collect();
+
+        let mut popped: Vec<u32> = workers
+            .into_iter()
+            .flat_map(|worker| worker.join().unwrap())
+            .collect();
+        popped.sort_unstable();
+        assert_eq!(popped, (0..1000).collect::<Vec<_>>());
+    }
+
+    #[test]
+    fn test_alter_all() {
+        let map: ConcurrentHashMap<u32, String, RandomState, 4> =
+            (0..10).map(|i| (i, i.to_string())).collect();
+        map.alter_all(|k, v| format!("{}:{}", k, v.len()));
+
+        assert_eq!(*map.get(&3).unwrap(), "3:1");
+        assert_eq!(map.fold(0, |n, (_, v)| n + v.contains(':') as usize), 10);
+    }
+
+    #[test]
+    fn test_merge_across_configurations() {
+        let global: ConcurrentHashMap<u32, u32, RandomState, 4> = (0..10).map(|i| (i, 1)).collect();
+        let worker: ConcurrentHashMap<u32, u32, RandomState, 2> = (5..15).map(|i| (i, 2)).collect();
+
+        global.merge(worker, |k, mine, theirs| {
+            assert!((5..10).contains(k));
+            mine + theirs
+        });
+
+        assert_eq!(*global.get(&0).unwrap(), 1);
+        assert_eq!(*global.get(&7).unwrap(), 3);
+        assert_eq!(*global.get(&14).unwrap(), 2);
+        assert_eq!(global.total_len(), 15);
+    }
+
+    #[test]
+    fn test_drain_into_other_shard_count() {
+        let current: ConcurrentHashMap<u32, u32, RandomState, 4> =
+            (0..100).map(|i| (i, i)).collect();
+        let previous: ConcurrentHashMap<u32, u32, RandomState, 8> = ConcurrentHashMap::default();
+
+        current.drain_into(&previous);
+        assert_eq!(current.total_len(), 0);
+        assert_eq!(previous.total_len(), 100);
+        assert_eq!(*previous.get(&42).unwrap(), 42);
+
+        // draining into itself leaves the contents unchanged
+        previous.drain_into(&previous);
+        assert_eq!(previous.total_len(), 100);
+    }
+
+    #[test]
+    fn test_from_hash_map_parallel() {
+        let source: HashMap<u32, u32> = (0..1000).map(|i| (i, i)).collect();
+        let hasher = source.hasher().clone();
+
+        let map: ConcurrentHashMap<u32, u32, RandomState, 8> =
+            ConcurrentHashMap::from_hash_map_parallel(source.clone(), 3);
+        assert_eq!(map, source);
+        assert_eq!(map.hasher().hash_one(7), hasher.hash_one(7));
+
+        let empty: ConcurrentHashMap<u32, u32, RandomState, 8> =
+            ConcurrentHashMap::from_hash_map_parallel(HashMap::new(), 0);
+        assert_eq!(empty.total_len(), 0);
+    }
+
+    #[test]
+    fn test_get_or_try_insert_with_runs_once() {
+        let map: Arc<ConcurrentHashMap<u32, u32, RandomState, 2>> =
+            Arc::new(ConcurrentHashMap::default());
+        let calls = Arc::new(AtomicUsize::new(0));
+
+        let workers: Vec<_> = (0..4)
+            .map(|_| {
+                let (map, calls) = (map.clone(), calls.clone());
+                std::thread::spawn(move || {
+                    for key in 0..50 {
+                        let value = map.get_or_try_insert_with(key, || {
+                            calls.fetch_add(1, Ordering::SeqCst);
+                            Ok::<_, ()>(key + 1)
+                        });
+                        assert_eq!(*value.unwrap(), key + 1);
+                    }
+                })
+            })
+            .collect();
+
+        for worker in workers {
+            worker.join().unwrap();
+        }
+        assert_eq!(calls.load(Ordering::SeqCst), 50);
+    }
+
+    #[test]
+    fn test_get_or_try_insert_with_lets_readers_in() {
+        let map: ConcurrentHashMap<u32, u32, RandomState, 1> = ConcurrentHashMap::default();
+        map.insert(1, 1);
+
+        let loaded = map.get_or_try_insert_with(2, || Ok::<_, ()>(2)).unwrap();
+        // single shard, so this would fail if the write lock were still held
+        std::thread::scope(|scope| {
+            scope.spawn(|| {
+                assert_eq!(*map.try_get(&1).unwrap().unwrap(), 1);
+                assert_eq!(*map.try_get(&2).unwrap().unwrap(), 2);
+            });
+        });
+        assert_eq!(*loaded, 2);
+    }
+
+    #[test]
+    fn test_non_default_shard_counts() {
+        fn exercise<const N: usize>() {
+            let map: ConcurrentHashMap<u32, u32, RandomState, N> =
+                ConcurrentHashMap::with_capacity_and_hasher(1000, RandomState::new());
+            assert_eq!(map.shards.len(), N);
+            assert_eq!(map.config().shard_count, N);
+            assert!(map.capacity_per_shard().iter().all(|&c| c >= 1000 / N));
+
+            map.insert_batch((0..1000).map(|i| (i, i)));
+            assert!((0..1000).all(|i| *map.get(&i).unwrap() == i));
+            assert_eq!(map.remove(&500), Some(500));
+            assert_eq!(map.total_len(), 999);
+        }
+
+        exercise::<1>();
+        exercise::<3>();
+        exercise::<7>();
+        exercise::<64>();
+        exercise::<300>();
+    }
+
+    #[test]
+    fn test_shard_index_ignores_bucket_and_tag_bits() {
+        // bits hashbrown uses for bucket selection (low) and tags (top 7) don't move the shard
+        let shard = |hash: u64, n: usize| HashSelector.shard(hash, &(), n);
+        let hash = 0x0123_4567_89ab_cdefu64;
+        assert_eq!(shard(hash, 128), shard(hash ^ 0xffff, 128));
+        assert_eq!(shard(hash, 128), shard(hash ^ (0x7f << 57), 128));
+        assert_eq!(shard(u64::MAX, 1), 0);
+        assert_eq!(shard(hash, 128), (hash >> 32) as usize & 127);
+        assert_eq!(shard(hash, 128), shard(hash ^ 0xffff_ffff, 128));
+        assert_eq!(shard(hash, 128), (hash >> 32) as usize & 127);
+        assert_eq!(shard(hash, 128), shard(hash ^ 0xffff_ffff, 128));
+
+        let mut hits = [0usize; 7];
+        for i in 0..7000u64 {
+            hits[shard(RandomState::new().hash_one(i), 7)] += 1;
+        }
+        assert!(hits.iter().all(|&n| n > 700), "{:?}", hits);
+    }
+
+    #[test]
+    fn test_shard_selector_routes_by_tenant() {
+        struct ByTenant;
+
+        impl ShardSelector<(u32, u32)> for ByTenant {
+            fn shard(&self, _hash: u64, key: &(u32, u32), n: usize) -> usize {
+                key.0 as usize % n
+            }
+        }
+
+        let map = ConcurrentHashMap::builder()
+            .shards::<4>()
+            .selector(ByTenant)
+            .build();
+        map.insert_batch((0..100).map(|user| ((1, user), user)));
+        map.insert((2, 0), 0);
+
+        let capacities = map.capacity_per_shard();
+        assert!(capacities[1] >= 100);
+        assert_eq!(capacities[0] + capacities[3], 0);
+
+        // every path has to agree with the selector
+        assert_eq!(*map.get(&(1, 42)).unwrap(), 42);
+        assert_eq!(map.read_all().get(&(2, 0)), Some(&0));
+        map.transact(&[&(1, 1), &(2, 0)], |tx| tx.insert((2, 1), 1));
+        assert_eq!(map.remove(&(2, 1)), Some(1));
+        assert_eq!(map.total_len(), 101);
+    }
+
+    #[test]
+    fn test_out_of_range_selector_is_clamped() {
+        struct Overshoot;
+
+        impl ShardSelector<u32> for Overshoot {
+            fn shard(&self, _hash: u64, key: &u32, n: usize) -> usize {
+                *key as usize % (2 * n)
+            }
+        }
+
+        let map = ConcurrentHashMap::builder()
+            .shards::<4>()
+            .selector(Overshoot)
+            .build();
+        map.insert_batch((0..100u32).map(|i| (i, i)));
+
+        assert!((0..100).all(|i| map.get(&i).map(|v| *v) == Some(i)));
+        assert!(map.capacity_per_shard()[3] >= 50);
+        assert_eq!(map.remove(&7), Some(7));
+        assert_eq!(map.total_len(), 99);
+    }
+
+    #[test]
+    fn test_modulo_selector_round_robins_sequential_ids() {
+        let map = ConcurrentHashMap::builder()
+            .shards::<3>()
+            .selector(ModuloSelector)
+            .build();
+        map.insert_batch((0..30u64).map(|id| (id, id)));
+
+        // the view walks shards in index order, so shard `k % 3` keys come out grouped
+        let residues: Vec<u64> = map.read_all().iter().map(|(k, _)| k % 3).collect();
+        assert_eq!(residues.len(), 30);
+        assert!(residues.windows(2).all(|w| w[0] <= w[1]));
+        assert_eq!(map.remove(&7), Some(7));
+        assert!(map.get(&8).is_some());
+    }
+
+    #[test]
+    fn test_mixing_selector_spreads_identity_hashes() {
+        use nohash_hasher::BuildNoHashHasher;
+
+        let aliased = ConcurrentHashMap::builder()
+            .shards::<16>()
+            .hasher(BuildNoHashHasher::<u64>::default())
+            .build();
+        aliased.insert_batch((0..1600u64).map(|id| (id, id)));
+        assert_eq!(aliased.capacity_per_shard()[1..].iter().sum::<usize>(), 0);
+
+        let mixed = ConcurrentHashMap::builder()
+            .shards::<16>()
+            .hasher(BuildNoHashHasher::<u64>::default())
+            .selector(MixingSelector)
+            .build();
+        mixed.insert_batch((0..1600u64).map(|id| (id, id)));
+        assert!(mixed.capacity_per_shard().iter().all(|&c| c >= 50));
+        assert_eq!(*mixed.get(&1234).unwrap(), 1234);
+    }
+
+    #[test]
+    fn test_hasher_need_not_be_clone() {
+        // shards borrow the map's hasher instead of each holding a clone
+        #[derive(Default)]
+        struct Unique(RandomState);
+
+        impl BuildHasher for Unique {
+            type Hasher = <RandomState as BuildHasher>::Hasher;
+
+            fn build_hasher(&self) -> Self::Hasher {
+                self.0.build_hasher()
+            }
+        }
+
+        let map: ConcurrentHashMap<u32, u32, Unique, 4> = (0..1000).map(|i| (i, i)).collect();
+        map.reserve(1000);
+        assert_eq!(*map.get(&999).unwrap(), 999);
+        assert_eq!(map.into_inner().len(), 1000);
+    }
+
+    #[test]
+    fn test_with_capacity_parallel_matches_serial() {
+        for threads in [0, 1, 3, 7, 64] {
+            let map = ConcurrentHashMap::builder()
+                .shards::<7>()
+                .capacity(7000)
+                .build_parallel(threads);
+            assert!(map.capacity_per_shard().iter().all(|&c| c >= 1000));
+
+            map.insert_batch((0..100).map(|i| (i, i)));
+            assert_eq!(map.total_len(), 100);
+        }
+    }
+
+    #[test]
+    fn test_rebalance_fixes_skew() {
+        use nohash_hasher::BuildNoHashHasher;
+
+        let map = ConcurrentHashMap::builder()
+            .shards::<16>()
+            .hasher(BuildNoHashHasher::<u64>::default())
+            .build();
+        map.insert_batch((0..1600u64).map(|id| (id, id)));
+        assert!(map.is_skewed(20));
+
+        let map = map.rebalance_with_hasher(RandomState::new());
+        assert!(!map.is_skewed(20));
+        assert_eq!(map.total_len(), 1600);
+        assert_eq!(*map.get(&1599).unwrap(), 1599);
+    }
+
+    #[test]
+    fn test_rebalance_keeps_read_policy() {
+        let map: ConcurrentHashMap<u32, u32> = ConcurrentHashMap::builder()
+            .read_policy(ReadPolicy::PreferReaders)
+            .build();
+        map.insert(1, 1);
+
+        let map = map.rebalance_with_hasher(RandomState::new());
+        assert_eq!(map.read_policy(), ReadPolicy::PreferReaders);
+        assert_eq!(*map.get(&1).unwrap(), 1);
+    }
+
+    #[test]
+    fn test_jump_selector_moves_keys_only_to_new_shard() {
+        let shard = |hash: u64, n: usize| JumpSelector.shard(hash, &(), n);
+        let state = RandomState::new();
+
+        let mut moved = 0;
+        for key in 0..9000u32 {
+            let hash = state.hash_one(key);
+            let (before, after) = (shard(hash, 8), shard(hash, 9));
+            assert!(before < 8);
+            if before != after {
+                assert_eq!(after, 8);
+                moved += 1;
+            }
+        }
+        // about 1/9 of the keys
+        assert!((700..1300).contains(&moved), "{}", moved);
+        assert_eq!(shard(u64::MAX, 1), 0);
+    }
+
+    #[test]
+    fn test_shard_selection_is_platform_independent() {
+        // selectors work in u64 space, so these hold on 32-bit targets too, including hashes
+        // that only differ above bit 32
+        let cases = [
+            (0x0123_4567_89ab_cdef, [56, 4, 57]),
+            (0xffff_ffff_0000_0000, [99, 50, 38]),
+            (0x0000_0001_0000_0000, [0, 49, 62]),
+        ];
+        for (hash, expected) in cases {
+            let actual = [
+                HashSelector.shard(hash, &(), 100),
+                MixingSelector.shard(hash, &(), 100),
+                JumpSelector.shard(hash, &(), 100),
+            ];
+            assert_eq!(actual, expected, "{:#x}", hash);
+        }
+        assert_eq!(
+            ModuloSelector.shard(0x1_0000_0005u64, &0x1_0000_0005u64, 7),
+            2
+        );
+    }
+
+    #[test]
+    fn test_insert_and_get_lets_readers_in() {
+        let map: ConcurrentHashMap<u32, u32, RandomState, 1> = ConcurrentHashMap::default();
+        map.insert(1, 1);
+
+        let value = map.insert_and_get(1, 2);
+        // single shard, so this would fail if the write lock were still held
+        std::thread::scope(|scope| {
+            scope.spawn(|| assert_eq!(*map.try_get(&1).unwrap().unwrap(), 2));
+        });
+        assert_eq!(*value, 2);
+        drop(value);
+        assert_eq!(*map.get(&1).unwrap(), 2);
+
+        assert_eq!(*map.insert_and_get(3, 4), 4);
+        assert_eq!(map.total_len(), 2);
+    }
+
+    #[cfg(feature = "send_guard")]
+    #[test]
+    fn test_guards_are_send() {
+        fn assert_send<T: Send>(_: &T) {}
+
+        let map: ConcurrentHashMap<u32, u32> = ConcurrentHashMap::new();
+        map.insert(1, 1);
+
+        std::thread::scope(|scope| {
+            let guard = map.get(&1).unwrap();
+            assert_send(&guard);
+            scope.spawn(move || assert_eq!(*guard, 1)).join().unwrap();
+
+            let mut guard = map.get_mut(&1).unwrap();
+            assert_send(&guard);
+            scope.spawn(move || *guard += 1).join().unwrap();
+        });
+        assert_eq!(*map.get(&1).unwrap(), 2);
+    }
+
+    #[test]
+    fn test_prefer_readers_skips_queued_writers() {
+        for policy in [ReadPolicy::Fair, ReadPolicy::PreferReaders] {
+            let map: Arc<ConcurrentHashMap<u32, u32, RandomState, 1>> = Arc::new(
+                ConcurrentHashMap::builder()
+                    .shards::<1>()
+                    .read_policy(policy)
+                    .build(),
+            );
+            map.insert(1, 1);
+
+            let reader = map.get(&1).unwrap();
+            let writer = {
+                let map = map.clone();
+                std::thread::spawn(move || map.insert(1, 2))
+            };
+            // a queued writer marks the lock exclusive while it waits for `reader`
+            while !map.is_locked_exclusive(&1) {
+                std::thread::yield_now();
+            }
+
+            let skipped = map.try_get(&1).map(|v| *v.unwrap());
+            assert_eq!(skipped.is_ok(), policy == ReadPolicy::PreferReaders);
+            drop(reader);
+            writer.join().unwrap();
+            assert_eq!(*map.get(&1).unwrap(), 2);
         }
-        assert_eq!(*map.get(&"k").unwrap(), "v");
     }
 
     #[test]
@@ -0,0 +1,458 @@
+#[cfg(feature = "lock-timing")]
+use crate::timing::{AtomicHistogram, Timing};
+use parking_lot::{
+    MappedRwLockReadGuard, MappedRwLockWriteGuard, RwLock, RwLockReadGuard,
+    RwLockUpgradableReadGuard, RwLockWriteGuard,
+};
+#[cfg(all(debug_assertions, not(feature = "send_guard")))]
+use std::cell::RefCell;
+use std::fmt;
+use std::marker::PhantomData;
+use std::ops::{Deref, DerefMut};
+use std::time::Duration;
+#[cfg(feature = "lock-timing")]
+use std::time::Instant;
+
+#[cfg(all(debug_assertions, not(feature = "send_guard")))]
+thread_local! {
+    // addresses of the shard locks this thread holds
+    static HELD: RefCell<Vec<usize>> = const { RefCell::new(Vec::new()) };
+}
+
+/// The lock around one shard.
+///
+/// Every acquisition returns a [`Locked`] guard, and guards handed to the caller wrap it too,
+/// so the map knows which shards the current thread holds until the guard is dropped.
+///
+/// In debug builds, every acquisition without a timeout first checks that the current thread
+/// isn't already holding the lock, e.g. calling `insert` while holding a guard from `get` on
+/// the same shard, from a `for_each` callback, or while a [`ReadView`](crate::ReadView) is
+/// alive. That would deadlock, so it panics with a clear message instead. With the
+/// `send_guard` feature guards may be dropped on another thread, so the check is disabled.
+///
+/// With the `lock-timing` feature it also records how long acquisitions wait, and how long
+/// each guard holds the lock.
+pub(crate) struct ShardLock<T> {
+    lock: RwLock<T>,
+    policy: ReadPolicy,
+    #[cfg(feature = "lock-timing")]
+    timing: Timing,
+    // the shard's index and its offset from the start of the map, for deadlock reports
+    #[cfg(feature = "deadlock_detection")]
+    location: (usize, usize),
+}
+
+/// Whether readers queue behind waiting writers, see
+/// [`ConcurrentHashMapBuilder::read_policy`](crate::ConcurrentHashMapBuilder::read_policy).
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+#[non_exhaustive]
+pub enum ReadPolicy {
+    /// parking_lot's task-fair policy: once a writer is waiting, new readers queue behind it,
+    /// so writers can't be starved. Best for update and write heavy workloads.
+    #[default]
+    Fair,
+    /// New readers acquire the lock even while a writer is waiting, so reads never wait on a
+    /// queued writer, but a steady stream of readers can starve writers. Only for read heavy
+    /// workloads with rare writes.
+    PreferReaders,
+}
+
+/// Marks a shard lock as held by the current thread until dropped. Empty in release builds
+/// without `lock-timing`.
+struct Held<'a> {
+    #[cfg(all(debug_assertions, not(feature = "send_guard")))]
+    addr: usize,
+    #[cfg(feature = "lock-timing")]
+    since: (&'a AtomicHistogram, Instant),
+    _lock: PhantomData<&'a ()>,
+}
+
+impl<T> ShardLock<T> {
+    pub(crate) fn new(value: T) -> Self {
+        ShardLock {
+            lock: RwLock::new(value),
+            policy: ReadPolicy::Fair,
+            #[cfg(feature = "lock-timing")]
+            timing: Timing::default(),
+            #[cfg(feature = "deadlock_detection")]
+            location: (0, 0),
+        }
+    }
+
+    pub(crate) fn into_inner(self) -> T {
+        self.lock.into_inner()
+    }
+
+    pub(crate) fn policy(&self) -> ReadPolicy {
+        self.policy
+    }
+
+    pub(crate) fn set_policy(&mut self, policy: ReadPolicy) {
+        self.policy = policy;
+    }
+
+    #[cfg(feature = "lock-timing")]
+    pub(crate) fn timing(&self) -> &Timing {
+        &self.timing
+    }
+
+    /// Sets the shard's index and the offset of this lock from the address of the map.
+    #[cfg(feature = "deadlock_detection")]
+    pub(crate) fn set_location(&mut self, index: usize, offset: usize) {
+        self.location = (index, offset);
+    }
+
+    /// Runs a blocking `acquire`, recording how long it waited, and with `deadlock_detection`
+    /// which shard the thread is blocked on meanwhile
+    #[inline]
+    fn timed<'a, G>(&'a self, acquire: impl FnOnce(&'a RwLock<T>) -> G) -> G {
+        #[cfg(feature = "lock-timing")]
+        let start = Instant::now();
+        #[cfg(feature = "deadlock_detection")]
+        let guard = {
+            let (index, offset) = self.location;
+            let map = self as *const Self as usize - offset;
+            crate::deadlock::blocking(map, index, || acquire(&self.lock))
+        };
+        #[cfg(not(feature = "deadlock_detection"))]
+        let guard = acquire(&self.lock);
+        #[cfg(feature = "lock-timing")]
+        self.timing.wait.record(start.elapsed());
+        guard
+    }
+
+    #[inline]
+    pub(crate) fn read(&self) -> Locked<'_, RwLockReadGuard<'_, T>> {
+        self.assert_not_held();
+        self.locked(match self.policy {
+            ReadPolicy::Fair => self.timed(RwLock::read),
+            ReadPolicy::PreferReaders => self.timed(RwLock::read_recursive),
+        })
+    }
+
+    /// A read lock that may already be held by this thread, without the reentrancy check.
+    #[inline]
+    pub(crate) fn read_recursive(&self) -> Locked<'_, RwLockReadGuard<'_, T>> {
+        self.locked(self.timed(RwLock::read_recursive))
+    }
+
+    #[inline]
+    pub(crate) fn write(&self) -> Locked<'_, RwLockWriteGuard<'_, T>> {
+        self.assert_not_held();
+        self.locked(self.timed(RwLock::write))
+    }
+
+    #[inline]
+    pub(crate) fn upgradable_read(&self) -> Locked<'_, RwLockUpgradableReadGuard<'_, T>> {
+        self.assert_not_held();
+        self.locked(self.timed(RwLock::upgradable_read))
+    }
+
+    #[inline]
+    pub(crate) fn try_read(&self) -> Option<Locked<'_, RwLockReadGuard<'_, T>>> {
+        let guard = match self.policy {
+            ReadPolicy::Fair => self.lock.try_read(),
+            ReadPolicy::PreferReaders => self.lock.try_read_recursive(),
+        };
+        Some(self.locked(guard?))
+    }
+
+    #[inline]
+    pub(crate) fn try_read_for(
+        &self,
+        timeout: Duration,
+    ) -> Option<Locked<'_, RwLockReadGuard<'_, T>>> {
+        let guard = match self.policy {
+            ReadPolicy::Fair => self.timed(|lock| lock.try_read_for(timeout)),
+            ReadPolicy::PreferReaders => self.timed(|lock| lock.try_read_recursive_for(timeout)),
+        };
+        Some(self.locked(guard?))
+    }
+
+    #[inline]
+    pub(crate) fn try_write_for(
+        &self,
+        timeout: Duration,
+    ) -> Option<Locked<'_, RwLockWriteGuard<'_, T>>> {
+        let guard = self.timed(|lock| lock.try_write_for(timeout));
+        Some(self.locked(guard?))
+    }
+
+    #[inline]
+    pub(crate) fn is_locked(&self) -> bool {
+        self.lock.is_locked()
+    }
+
+    #[inline]
+    pub(crate) fn is_locked_exclusive(&self) -> bool {
+        self.lock.is_locked_exclusive()
+    }
+
+    /// Pairs an acquired `guard` with a record that the current thread holds this lock.
+    #[inline]
+    fn locked<G>(&self, guard: G) -> Locked<'_, G> {
+        #[cfg(all(debug_assertions, not(feature = "send_guard")))]
+        let addr = self.addr();
+        #[cfg(all(debug_assertions, not(feature = "send_guard")))]
+        HELD.with(|held| held.borrow_mut().push(addr));
+
+        Locked {
+            guard,
+            held: Held {
+                #[cfg(all(debug_assertions, not(feature = "send_guard")))]
+                addr,
+                #[cfg(feature = "lock-timing")]
+                since: (&self.timing.hold, Instant::now()),
+                _lock: PhantomData,
+            },
+        }
+    }
+
+    #[cfg(all(debug_assertions, not(feature = "send_guard")))]
+    fn addr(&self) -> usize {
+        &self.lock as *const RwLock<T> as usize
+    }
+
+    #[inline]
+    fn assert_not_held(&self) {
+        #[cfg(all(debug_assertions, not(feature = "send_guard")))]
+        if HELD.with(|held| held.borrow().contains(&self.addr())) {
+            panic!(
+                "shard is already locked by this thread, locking it again while holding a \
+                 guard, view, transaction or entry on the same shard, or from a callback, \
+                 would deadlock"
+            );
+        }
+    }
+}
+
+/// A guard over a shard lock that also marks the shard as held until dropped, see
+/// [`ShardLock`].
+pub(crate) struct Locked<'a, G> {
+    // dropped first, so the lock is released before it is unmarked
+    guard: G,
+    held: Held<'a>,
+}
+
+impl<'a, G> Locked<'a, G> {
+    /// Swaps the inner guard, keeping the mark.
+    #[inline]
+    fn with<H>(self, f: impl FnOnce(G) -> H) -> Locked<'a, H> {
+        Locked {
+            guard: f(self.guard),
+            held: self.held,
+        }
+    }
+}
+
+impl<'a, T: ?Sized> Locked<'a, RwLockReadGuard<'a, T>> {
+    #[inline]
+    pub(crate) fn try_map<U: ?Sized>(
+        self,
+        f: impl FnOnce(&T) -> Option<&U>,
+    ) -> Option<ReadGuard<'a, U>> {
+        let Locked { guard, held } = self;
+        let guard = RwLockReadGuard::try_map(guard, f).ok()?;
+        Some(ReadGuard(Locked { guard, held }))
+    }
+}
+
+impl<'a, T: ?Sized> Locked<'a, RwLockWriteGuard<'a, T>> {
+    #[inline]
+    pub(crate) fn map<U: ?Sized>(self, f: impl FnOnce(&mut T) -> &mut U) -> WriteGuard<'a, U> {
+        WriteGuard(self.with(|guard| RwLockWriteGuard::map(guard, f)))
+    }
+
+    #[inline]
+    pub(crate) fn try_map<U: ?Sized>(
+        self,
+        f: impl FnOnce(&mut T) -> Option<&mut U>,
+    ) -> Result<WriteGuard<'a, U>, Self> {
+        let Locked { guard, held } = self;
+        match RwLockWriteGuard::try_map(guard, f) {
+            Ok(guard) => Ok(WriteGuard(Locked { guard, held })),
+            Err(guard) => Err(Locked { guard, held }),
+        }
+    }
+
+    #[inline]
+    pub(crate) fn downgrade(self) -> Locked<'a, RwLockReadGuard<'a, T>> {
+        self.with(RwLockWriteGuard::downgrade)
+    }
+}
+
+impl<'a, T: ?Sized> Locked<'a, RwLockUpgradableReadGuard<'a, T>> {
+    #[inline]
+    pub(crate) fn upgrade(self) -> Locked<'a, RwLockWriteGuard<'a, T>> {
+        self.with(RwLockUpgradableReadGuard::upgrade)
+    }
+}
+
+impl<G: Deref> Deref for Locked<'_, G> {
+    type Target = G::Target;
+
+    #[inline]
+    fn deref(&self) -> &G::Target {
+        &self.guard
+    }
+}
+
+impl<G: DerefMut> DerefMut for Locked<'_, G> {
+    #[inline]
+    fn deref_mut(&mut self) -> &mut G::Target {
+        &mut self.guard
+    }
+}
+
+/// A guarded shared reference into the map, returned by [`get`](crate::ConcurrentHashMap::get)
+/// and similar lookups.
+///
+/// The shard stays locked until the guard is dropped. In debug builds, locking the same shard
+/// again from this thread while the guard is alive panics instead of deadlocking.
+pub struct ReadGuard<'a, T: ?Sized>(Locked<'a, MappedRwLockReadGuard<'a, T>>);
+
+/// A guarded mutable reference into the map, returned by
+/// [`get_mut`](crate::ConcurrentHashMap::get_mut) and similar lookups.
+///
+/// The shard stays write locked until the guard is dropped. In debug builds, locking the same
+/// shard again from this thread while the guard is alive panics instead of deadlocking.
+pub struct WriteGuard<'a, T: ?Sized>(Locked<'a, MappedRwLockWriteGuard<'a, T>>);
+
+impl<T: ?Sized> Deref for ReadGuard<'_, T> {
+    type Target = T;
+
+    #[inline]
+    fn deref(&self) -> &T {
+        &self.0
+    }
+}
+
+impl<T: ?Sized> Deref for WriteGuard<'_, T> {
+    type Target = T;
+
+    #[inline]
+    fn deref(&self) -> &T {
+        &self.0
+    }
+}
+
+impl<T: ?Sized> DerefMut for WriteGuard<'_, T> {
+    #[inline]
+    fn deref_mut(&mut self) -> &mut T {
+        &mut self.0
+    }
+}
+
+impl<T: fmt::Debug + ?Sized> fmt::Debug for ReadGuard<'_, T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        (**self).fmt(f)
+    }
+}
+
+impl<T: fmt::Display + ?Sized> fmt::Display for ReadGuard<'_, T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        (**self).fmt(f)
+    }
+}
+
+impl<T: fmt::Debug + ?Sized> fmt::Debug for WriteGuard<'_, T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        (**self).fmt(f)
+    }
+}
+
+impl<T: fmt::Display + ?Sized> fmt::Display for WriteGuard<'_, T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        (**self).fmt(f)
+    }
+}
+
+impl Drop for Held<'_> {
+    fn drop(&mut self) {
+        #[cfg(feature = "lock-timing")]
+        self.since.0.record(self.since.1.elapsed());
+
+        #[cfg(all(debug_assertions, not(feature = "send_guard")))]
+        HELD.with(|held| {
+            let mut held = held.borrow_mut();
+            if let Some(i) = held.iter().rposition(|&addr| addr == self.addr) {
+                held.swap_remove(i);
+            }
+        });
+    }
+}
+
+#[cfg(all(test, debug_assertions, not(feature = "send_guard")))]
+mod tests {
+    use crate::ConcurrentHashMap;
+    use std::collections::hash_map::RandomState;
+    use std::panic::{catch_unwind, AssertUnwindSafe};
+
+    #[test]
+    fn test_reentrant_calls_panic_instead_of_deadlocking() {
+        let map: ConcurrentHashMap<u32, u32, RandomState, 1> = ConcurrentHashMap::default();
+        map.insert(1, 1);
+
+        let result = catch_unwind(AssertUnwindSafe(|| {
+            map.for_each(|_, _| {
+                map.insert(2, 2);
+            })
+        }));
+        let message = result.unwrap_err();
+        assert!(message
+            .downcast_ref::<&str>()
+            .unwrap()
+            .contains("already locked by this thread"));
+
+        let view = map.read_all();
+        assert!(catch_unwind(AssertUnwindSafe(|| map.get(&1).is_some())).is_err());
+        drop(view);
+
+        // the locks were released while unwinding
+        map.insert(2, 2);
+        assert_eq!(map.with(&1, |v| *v), Some(1));
+        assert_eq!(*map.get(&2).unwrap(), 2);
+    }
+
+    #[test]
+    fn test_returned_guards_are_tracked() {
+        let map: ConcurrentHashMap<u32, u32, RandomState, 1> = ConcurrentHashMap::default();
+        map.insert(1, 1);
+
+        let guard = map.get(&1).unwrap();
+        assert!(catch_unwind(AssertUnwindSafe(|| map.insert(2, 2))).is_err());
+        drop(guard);
+
+        let guard = map.get_mut(&1).unwrap();
+        assert!(catch_unwind(AssertUnwindSafe(|| map.get(&1).is_some())).is_err());
+        drop(guard);
+
+        let update = crate::Op::Update(
+            1,
+            Box::new(|_: &mut u32| {
+                map.remove(&1);
+            }),
+        );
+        assert!(catch_unwind(AssertUnwindSafe(|| map.apply_batch(vec![update]))).is_err());
+
+        // dropping a guard unmarks its shard
+        map.insert(2, 2);
+        assert_eq!(*map.get(&1).unwrap(), 1);
+    }
+
+    #[test]
+    fn test_other_maps_and_threads_are_unaffected() {
+        let a: ConcurrentHashMap<u32, u32, RandomState, 1> = ConcurrentHashMap::default();
+        let b: ConcurrentHashMap<u32, u32, RandomState, 1> = ConcurrentHashMap::default();
+        a.insert(1, 1);
+
+        a.for_each(|k, v| {
+            b.insert(*k, *v);
+            std::thread::scope(|scope| {
+                scope.spawn(|| assert!(a.try_get(k).unwrap().is_some()));
+            });
+        });
+        assert_eq!(*b.get(&1).unwrap(), 1);
+    }
+}
@@ -0,0 +1,139 @@
+//! Write-once values per key.
+//!
+//! This stops short of a futures-aware, lock-free once map. Finding a key's cell takes a brief
+//! shard read lock on every call, because a lock-free index would need `unsafe` or an
+//! `arc-swap` style dependency. Initialization goes through [`OnceLock`], which parks the
+//! calling thread while another caller's initializer runs, so there is no `async` variant yet.
+
+use crate::{ConcurrentHashMap, Equivalent, DEFAULT_SHARD_COUNT};
+use std::collections::hash_map::RandomState;
+use std::hash::{BuildHasher, Hash};
+use std::sync::{Arc, OnceLock};
+
+/// A sharded map of write-once values.
+///
+/// [`get_or_init`](ShardedOnceMap::get_or_init) runs the initializer at most once per key, even
+/// when many callers race on the same key, and the value can never be replaced afterwards.
+/// Values are handed out as `Arc<V>`, so holding one never holds a shard lock.
+///
+/// Looking a value up is not lock-free: every call briefly read-locks the key's shard to find
+/// its cell. A caller that races an in-flight initializer blocks its thread until it finishes,
+/// so async code with slow initializers should call `get_or_init` from a blocking context.
+///
+/// # Examples
+///
+/// ```
+/// use sharded::ShardedOnceMap;
+///
+/// let schemas = ShardedOnceMap::new();
+/// let schema = schemas.get_or_init("users", || vec!["id", "name"]);
+/// let again = schemas.get_or_init("users", || unreachable!());
+/// assert_eq!(schema, again);
+/// ```
+pub struct ShardedOnceMap<K, V, S = RandomState, const N: usize = DEFAULT_SHARD_COUNT> {
+    cells: ConcurrentHashMap<K, Arc<OnceLock<Arc<V>>>, S, N>,
+}
+
+impl<K, V> ShardedOnceMap<K, V, RandomState, DEFAULT_SHARD_COUNT> {
+    /// Creates an empty `ShardedOnceMap`.
+    #[must_use]
+    pub fn new() -> ShardedOnceMap<K, V, RandomState> {
+        Default::default()
+    }
+}
+
+impl<K, V, S, const N: usize> Default for ShardedOnceMap<K, V, S, N>
+where
+    S: Default + BuildHasher,
+{
+    fn default() -> Self {
+        ShardedOnceMap {
+            cells: ConcurrentHashMap::default(),
+        }
+    }
+}
+
+impl<K, V, S: BuildHasher, const N: usize> ShardedOnceMap<K, V, S, N> {
+    /// Returns the value for `key`, running `f` to create it if this is the first call for the
+    /// key.
+    ///
+    /// Concurrent callers for the same key block until the winner's `f` finishes and then all
+    /// observe its value. The shard lock is not held while `f` runs, so other keys in the same
+    /// shard are unaffected. If `f` panics, the key stays uninitialized and a later call may
+    /// try again.
+    ///
+    /// **Locks** - Acquires a read lock on one of `N` shards, plus a write lock the first time
+    /// the key is seen.
+    pub fn get_or_init<F>(&self, key: K, f: F) -> Arc<V>
+    where
+        K: Hash + Eq,
+        F: FnOnce() -> V,
+    {
+        let cell = match self.cells.get_cloned(&key) {
+            Some(cell) => cell,
+            None => match self.cells.try_insert(key, Arc::default()) {
+                Ok(cell) => cell.clone(),
+                Err(occupied) => occupied.existing.clone(),
+            },
+        };
+
+        cell.get_or_init(|| Arc::new(f())).clone()
+    }
+
+    /// Returns the value for `key` if it has been initialized.
+    ///
+    /// **Locks** - Acquires a read lock on one of `N` shards.
+    pub fn get<Q>(&self, key: &Q) -> Option<Arc<V>>
+    where
+        Q: Hash + Equivalent<K> + ?Sized,
+    {
+        self.cells.with(key, |cell| cell.get().cloned()).flatten()
+    }
+
+    /// Returns `true` if the value for `key` has been initialized.
+    ///
+    /// **Locks** - Acquires a read lock on one of `N` shards.
+    pub fn contains_key<Q>(&self, key: &Q) -> bool
+    where
+        Q: Hash + Equivalent<K> + ?Sized,
+    {
+        self.cells
+            .with(key, |cell| cell.get().is_some())
+            .unwrap_or(false)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    #[test]
+    fn test_initializes_once_under_contention() {
+        let map = Arc::new(ShardedOnceMap::<u32, u32>::new());
+        let calls = Arc::new(AtomicUsize::new(0));
+
+        let workers: Vec<_> = (0..8)
+            .map(|_| {
+                let (map, calls) = (map.clone(), calls.clone());
+                std::thread::spawn(move || {
+                    for key in 0..100 {
+                        let value = map.get_or_init(key, || {
+                            calls.fetch_add(1, Ordering::SeqCst);
+                            key * 2
+                        });
+                        assert_eq!(*value, key * 2);
+                    }
+                })
+            })
+            .collect();
+
+        for worker in workers {
+            worker.join().unwrap();
+        }
+
+        assert_eq!(calls.load(Ordering::SeqCst), 100);
+        assert_eq!(map.get(&7).as_deref(), Some(&14));
+        assert!(!map.contains_key(&100));
+    }
+}
@@ -0,0 +1,210 @@
+use crate::{
+    make_hash, make_hasher, ConcurrentHashMap, Equivalent, HashSelector, Notifier, ShardSelector,
+    ShardWriteGuard,
+};
+use hashbrown::hash_table::{Entry, OccupiedEntry, VacantEntry};
+use std::hash::{BuildHasher, Hash};
+
+/// A builder for a [`RawEntryMut`], with full control over hashing and key matching.
+///
+/// This `struct` is created by [`ConcurrentHashMap::raw_entry_mut`]. Looking up an entry
+/// write locks its shard, and the builder keeps that lock until it is dropped.
+pub struct RawEntryBuilderMut<'a, K, V, S, const N: usize> {
+    map: &'a ConcurrentHashMap<K, V, S, N>,
+    shard: Option<ShardWriteGuard<'a, K, V>>,
+}
+
+/// A view into a single entry of the map, which may be vacant or occupied.
+///
+/// The entry borrows the builder that holds the write lock of its shard, so it is exclusive
+/// for every key in that shard.
+pub enum RawEntryMut<'a, K, V> {
+    /// An occupied entry.
+    Occupied(RawOccupiedEntryMut<'a, K, V>),
+    /// A vacant entry.
+    Vacant(RawVacantEntryMut<'a, K, V>),
+}
+
+/// A view into an occupied entry, part of the [`RawEntryMut`] enum.
+pub struct RawOccupiedEntryMut<'a, K, V> {
+    entry: OccupiedEntry<'a, (K, V)>,
+}
+
+/// A view into a vacant entry, part of the [`RawEntryMut`] enum.
+pub struct RawVacantEntryMut<'a, K, V> {
+    entry: VacantEntry<'a, (K, V)>,
+    notifier: &'a Notifier,
+}
+
+impl<K, V, S, const N: usize> ConcurrentHashMap<K, V, S, N> {
+    /// Creates a raw entry builder, for lookups where the caller computes the hash and decides
+    /// which stored key matches, e.g. interning or composite equality.
+    ///
+    /// The hash passed to the builder *must* be the one the map's hasher produces for the key
+    /// that ends up stored, see [`ConcurrentHashMap::hasher`]. Otherwise later lookups will not
+    /// find the entry.
+    ///
+    /// Raw entries route by hash alone, so they are only available with the default
+    /// [`HashSelector`].
+    ///
+    /// **Locks** - Looking up an entry acquires a write lock on one of `N` shards, which the
+    /// builder holds until it is dropped.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use sharded::{ConcurrentHashMap, RawEntryMut};
+    /// use std::hash::BuildHasher;
+    ///
+    /// let interned: ConcurrentHashMap<String, usize> = ConcurrentHashMap::new();
+    ///
+    /// let intern = |s: &str| {
+    ///     let hash = interned.hasher().hash_one(s);
+    ///     match interned.raw_entry_mut().from_hash(hash, |k| k == s) {
+    ///         RawEntryMut::Occupied(entry) => *entry.get(),
+    ///         RawEntryMut::Vacant(entry) => *entry.insert(s.to_owned(), hash as usize),
+    ///     }
+    /// };
+    ///
+    /// assert_eq!(intern("a"), intern("a"));
+    /// assert_eq!(interned.get("a").map(|v| *v), Some(intern("a")));
+    /// ```
+    pub fn raw_entry_mut(&self) -> RawEntryBuilderMut<'_, K, V, S, N> {
+        RawEntryBuilderMut {
+            map: self,
+            shard: None,
+        }
+    }
+}
+
+impl<'a, K: Hash, V, S: BuildHasher, const N: usize> RawEntryBuilderMut<'a, K, V, S, N> {
+    /// Looks up the entry with the given hash, using `is_match` to compare stored keys.
+    ///
+    /// A shard locked by an earlier lookup on this builder is released first.
+    pub fn from_hash<F>(&mut self, hash: u64, mut is_match: F) -> RawEntryMut<'_, K, V>
+    where
+        F: FnMut(&K) -> bool,
+    {
+        let i = HashSelector.shard(hash, &(), N);
+
+        self.shard = None;
+        let shard = self.shard.insert(self.map.shards[i].write());
+
+        match shard.inner.entry(
+            hash,
+            |(k, _)| is_match(k),
+            make_hasher::<K, V, S>(&self.map.hash_builder),
+        ) {
+            Entry::Occupied(entry) => RawEntryMut::Occupied(RawOccupiedEntryMut { entry }),
+            Entry::Vacant(entry) => RawEntryMut::Vacant(RawVacantEntryMut {
+                entry,
+                notifier: &self.map.notifiers[i],
+            }),
+        }
+    }
+
+    /// Looks up the entry for `key`, hashing it with the map's hasher.
+    pub fn from_key<Q>(&mut self, key: &Q) -> RawEntryMut<'_, K, V>
+    where
+        Q: Hash + Equivalent<K> + ?Sized,
+    {
+        let hash = make_hash::<Q, _>(&self.map.hash_builder, key);
+        self.from_hash(hash, |k| key.equivalent(k))
+    }
+}
+
+impl<'a, K, V> RawOccupiedEntryMut<'a, K, V> {
+    /// Gets a reference to the key in the entry.
+    pub fn key(&self) -> &K {
+        &self.entry.get().0
+    }
+
+    /// Gets a reference to the value in the entry.
+    pub fn get(&self) -> &V {
+        &self.entry.get().1
+    }
+
+    /// Gets a mutable reference to the value in the entry.
+    pub fn get_mut(&mut self) -> &mut V {
+        &mut self.entry.get_mut().1
+    }
+
+    /// Sets the value of the entry, returning the old value.
+    pub fn insert(&mut self, value: V) -> V {
+        std::mem::replace(self.get_mut(), value)
+    }
+
+    /// Converts the entry into a mutable reference to its value, valid for as long as the
+    /// builder's lock.
+    pub fn into_mut(self) -> &'a mut V {
+        &mut self.entry.into_mut().1
+    }
+
+    /// Takes the value out of the map.
+    pub fn remove(self) -> V {
+        self.remove_entry().1
+    }
+
+    /// Takes the key value pair out of the map.
+    pub fn remove_entry(self) -> (K, V) {
+        self.entry.remove().0
+    }
+}
+
+impl<'a, K, V> RawVacantEntryMut<'a, K, V> {
+    /// Inserts the key value pair under the hash the entry was looked up with, returning a
+    /// mutable reference to the value.
+    pub fn insert(self, key: K, value: V) -> &'a mut V {
+        let value = &mut self.entry.insert((key, value)).into_mut().1;
+        self.notifier.notify();
+        value
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::hash_map::RandomState;
+
+    #[test]
+    fn test_raw_entry_round_trip() {
+        let map: ConcurrentHashMap<(u32, u32), &str, RandomState, 4> = ConcurrentHashMap::default();
+
+        // match on the first component only
+        let hash = map.hasher().hash_one((1, 0));
+        match map.raw_entry_mut().from_hash(hash, |k| k.0 == 1) {
+            RawEntryMut::Vacant(entry) => *entry.insert((1, 0), "a") = "b",
+            RawEntryMut::Occupied(_) => panic!("expected vacant"),
+        }
+        assert_eq!(*map.get(&(1, 0)).unwrap(), "b");
+
+        match map.raw_entry_mut().from_key(&(1, 0)) {
+            RawEntryMut::Occupied(mut entry) => {
+                assert_eq!(entry.key(), &(1, 0));
+                assert_eq!(entry.insert("c"), "b");
+                assert_eq!(entry.remove_entry(), ((1, 0), "c"));
+            }
+            RawEntryMut::Vacant(_) => panic!("expected occupied"),
+        }
+        assert!(map.get(&(1, 0)).is_none());
+    }
+
+    #[test]
+    fn test_raw_entry_builder_relocks_between_lookups() {
+        let map: ConcurrentHashMap<u32, u32, RandomState, 1> = ConcurrentHashMap::default();
+
+        let mut entries = map.raw_entry_mut();
+        if let RawEntryMut::Vacant(entry) = entries.from_key(&1) {
+            *entry.insert(1, 1) += 1;
+        }
+        // the same shard again, the first lookup's lock is released before relocking
+        match entries.from_key(&1) {
+            RawEntryMut::Occupied(entry) => assert_eq!(entry.remove(), 2),
+            RawEntryMut::Vacant(_) => panic!("expected occupied"),
+        }
+        assert!(map.try_get(&1).is_err());
+
+        drop(entries);
+        assert!(map.get(&1).is_none());
+    }
+}
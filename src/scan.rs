@@ -0,0 +1,114 @@
+use crate::ConcurrentHashMap;
+use std::hash::BuildHasher;
+
+/// A position in a [`ConcurrentHashMap::scan`], resumable across calls.
+///
+/// `Cursor::default()` starts a new scan.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct Cursor {
+    shard: usize,
+    bucket: usize,
+}
+
+impl<K, V, S: BuildHasher, const N: usize, P> ConcurrentHashMap<K, V, S, N, P> {
+    /// Returns clones of up to `limit` entries starting at `cursor`, and the cursor to resume
+    /// from, or `None` once every shard has been visited.
+    ///
+    /// No lock is held between calls, so huge maps can be walked in slices while writers make
+    /// progress. Entries present for the whole scan are returned exactly once, provided their
+    /// shard doesn't resize while the scan is in it. Inserts can make a shard grow, after which
+    /// some of its entries may be returned twice or skipped. Entries inserted or removed
+    /// meanwhile may or may not be returned.
+    ///
+    /// **Locks** - Acquires a read lock on each visited shard, one at a time.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `limit` is 0, since the scan could never make progress.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use sharded::{ConcurrentHashMap, Cursor};
+    ///
+    /// let map: ConcurrentHashMap<_, _> = (0..100).map(|i| (i, i)).collect();
+    ///
+    /// let mut seen = 0;
+    /// let mut cursor = Some(Cursor::default());
+    /// while let Some(at) = cursor {
+    ///     let (entries, next) = map.scan(at, 16);
+    ///     assert!(entries.len() <= 16);
+    ///     seen += entries.len();
+    ///     cursor = next;
+    /// }
+    /// assert_eq!(seen, 100);
+    /// ```
+    pub fn scan(&self, cursor: Cursor, limit: usize) -> (Vec<(K, V)>, Option<Cursor>)
+    where
+        K: Clone,
+        V: Clone,
+    {
+        assert!(limit > 0, "scan limit must be > 0");
+        let mut entries = Vec::new();
+
+        for shard in cursor.shard..N {
+            let guard = self.shards[shard].read();
+            let start = if shard == cursor.shard {
+                cursor.bucket
+            } else {
+                0
+            };
+
+            for bucket in start..guard.inner.num_buckets() {
+                if let Some(pair) = guard.inner.get_bucket(bucket) {
+                    if entries.len() == limit {
+                        return (entries, Some(Cursor { shard, bucket }));
+                    }
+                    entries.push(pair.clone());
+                }
+            }
+        }
+
+        (entries, None)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::hash_map::RandomState;
+
+    #[test]
+    fn test_scan_with_concurrent_removals() {
+        let map: ConcurrentHashMap<u32, u32, RandomState, 4> = (0..1000).map(|i| (i, i)).collect();
+
+        let mut seen = Vec::new();
+        let mut cursor = Some(Cursor::default());
+        while let Some(at) = cursor {
+            let (entries, next) = map.scan(at, 7);
+            // removals never shrink the table, so positions stay valid
+            for (k, _) in &entries {
+                map.remove(&(k + 1));
+            }
+            seen.extend(entries.into_iter().map(|(k, _)| k));
+            cursor = next;
+        }
+
+        // every surviving key was returned exactly once
+        let mut survivors = Vec::new();
+        map.for_each(|k, _| survivors.push(*k));
+        survivors.sort_unstable();
+        seen.sort_unstable();
+        let total = seen.len();
+        seen.dedup();
+        assert_eq!(seen.len(), total);
+        assert!(survivors.iter().all(|k| seen.binary_search(k).is_ok()));
+    }
+
+    #[test]
+    #[should_panic(expected = "scan limit must be > 0")]
+    fn test_scan_rejects_zero_limit() {
+        let map: ConcurrentHashMap<u32, u32> = (0..10).map(|i| (i, i)).collect();
+        map.scan(Cursor::default(), 0);
+    }
+}
@@ -0,0 +1,180 @@
+/// Routes keys to shards.
+///
+/// The selector is a type parameter of [`ConcurrentHashMap`](crate::ConcurrentHashMap), so
+/// keys can be placed deliberately, e.g. every key of one tenant in the same shard so that
+/// per-tenant work only ever contends on one lock. It must return an index in `0..n`, and must
+/// return the same index for a lookup key as for every key it is [`Equivalent`](crate::Equivalent)
+/// to, or lookups will miss.
+///
+/// # Examples
+///
+/// ```
+/// use sharded::{ConcurrentHashMap, ShardSelector};
+///
+/// struct ByTenant;
+///
+/// impl ShardSelector<(u32, &str)> for ByTenant {
+///     fn shard(&self, _hash: u64, key: &(u32, &str), n: usize) -> usize {
+///         key.0 as usize % n
+///     }
+/// }
+///
+/// let map = ConcurrentHashMap::builder()
+///     .shards::<8>()
+///     .selector(ByTenant)
+///     .build();
+/// map.insert((3, "alice"), 1);
+/// map.insert((3, "bob"), 2);
+///
+/// let used = map.capacity_per_shard().iter().filter(|&&c| c > 0).count();
+/// assert_eq!(used, 1);
+/// ```
+pub trait ShardSelector<K: ?Sized> {
+    /// Returns the index, in `0..n`, of the shard owning `key`, whose hash under the map's
+    /// hasher is `hash`.
+    fn shard(&self, hash: u64, key: &K, n: usize) -> usize;
+}
+
+/// Asks `selector` for the shard of `key`, clamped into `0..n` so indexing the shards can't
+/// fail. A selector returning out of range indices still routes every key consistently.
+#[inline]
+pub(crate) fn select_shard<K, P>(selector: &P, hash: u64, key: &K, n: usize) -> usize
+where
+    K: ?Sized,
+    P: ShardSelector<K>,
+{
+    selector.shard(hash, key, n).min(n.saturating_sub(1))
+}
+
+/// The default [`ShardSelector`], which routes by hash alone.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct HashSelector;
+
+impl<K: ?Sized> ShardSelector<K> for HashSelector {
+    /// hashbrown picks buckets from the low bits and tags them with the top 7, so the shard is
+    /// taken from the upper half of the hash, below the tag. Power of two shard counts, like
+    /// the default, mask those bits. Other counts stay supported, since `N` is an array length
+    /// that can't be rounded, and scale the bits below the tag by `n` with a widening multiply
+    /// instead, which is still division free.
+    #[inline]
+    fn shard(&self, hash: u64, _key: &K, n: usize) -> usize {
+        if n.is_power_of_two() {
+            (hash >> 32) as usize & (n - 1)
+        } else {
+            (((hash << 7) as u128 * n as u128) >> 64) as usize
+        }
+    }
+}
+
+/// A [`ShardSelector`] that scrambles the hash before picking a shard.
+///
+/// [`HashSelector`] relies on the hasher filling the high bits, which identity hashers for
+/// integer or pre-hashed keys (e.g. `nohash_hasher::BuildNoHashHasher`) don't: small keys would
+/// all alias to shard 0. This multiplies by a 64-bit golden ratio constant first, so every bit
+/// of the hash moves the shard.
+///
+/// # Examples
+///
+/// ```
+/// use sharded::{ConcurrentHashMap, MixingSelector};
+/// use std::hash::{BuildHasherDefault, Hasher};
+///
+/// #[derive(Default)]
+/// struct Identity(u64);
+///
+/// impl Hasher for Identity {
+///     fn finish(&self) -> u64 {
+///         self.0
+///     }
+///     fn write(&mut self, _: &[u8]) {
+///         unimplemented!()
+///     }
+///     fn write_u64(&mut self, n: u64) {
+///         self.0 = n
+///     }
+/// }
+///
+/// let map = ConcurrentHashMap::builder()
+///     .shards::<8>()
+///     .hasher(BuildHasherDefault::<Identity>::default())
+///     .selector(MixingSelector)
+///     .build();
+/// map.insert_batch((0..800u64).map(|id| (id, id)));
+///
+/// assert!(map.capacity_per_shard().iter().all(|&c| c > 0));
+/// ```
+#[derive(Clone, Copy, Debug, Default)]
+pub struct MixingSelector;
+
+impl<K: ?Sized> ShardSelector<K> for MixingSelector {
+    #[inline]
+    fn shard(&self, hash: u64, _key: &K, n: usize) -> usize {
+        let mixed = hash.wrapping_mul(0x9e37_79b9_7f4a_7c15);
+        ((mixed as u128 * n as u128) >> 64) as usize
+    }
+}
+
+/// A [`ShardSelector`] for integer keys that routes key `k` to shard `k % n`.
+///
+/// Sequential ids land in consecutive shards, spreading load evenly without depending on the
+/// quality of the hasher's output. The hasher still runs once per operation, for hashbrown's
+/// bucket lookup within the shard.
+///
+/// # Examples
+///
+/// ```
+/// use sharded::{ConcurrentHashMap, ModuloSelector};
+///
+/// let map = ConcurrentHashMap::builder()
+///     .shards::<4>()
+///     .selector(ModuloSelector)
+///     .build();
+/// map.insert_batch((0..400u32).map(|id| (id, id)));
+///
+/// assert!(map.capacity_per_shard().iter().all(|&c| c >= 100));
+/// ```
+#[derive(Clone, Copy, Debug, Default)]
+pub struct ModuloSelector;
+
+impl<K: Copy + Into<u64>> ShardSelector<K> for ModuloSelector {
+    #[inline]
+    fn shard(&self, _hash: u64, key: &K, n: usize) -> usize {
+        ((*key).into() % n as u64) as usize
+    }
+}
+
+/// A [`ShardSelector`] using jump consistent hashing, so that going from `n` to `n + 1` shards
+/// moves only about `1 / (n + 1)` of the keys, all of them into the new shard.
+///
+/// Useful when shard indices outlive one map, e.g. with
+/// [`export_shard`](crate::ConcurrentHashMap::export_shard) or shard-indexed storage, at the
+/// cost of `O(log n)` steps per lookup. See Lamping and Veach, "A Fast, Minimal Memory,
+/// Consistent Hash Algorithm".
+///
+/// # Examples
+///
+/// ```
+/// use sharded::{ConcurrentHashMap, JumpSelector};
+///
+/// let map = ConcurrentHashMap::builder()
+///     .shards::<16>()
+///     .selector(JumpSelector)
+///     .build();
+/// map.insert("a", 1);
+/// assert_eq!(*map.get(&"a").unwrap(), 1);
+/// ```
+#[derive(Clone, Copy, Debug, Default)]
+pub struct JumpSelector;
+
+impl<K: ?Sized> ShardSelector<K> for JumpSelector {
+    #[inline]
+    fn shard(&self, hash: u64, _key: &K, n: usize) -> usize {
+        let (mut key, mut b, mut j) = (hash, 0u64, 0u64);
+        while j < n as u64 {
+            b = j;
+            key = key.wrapping_mul(2862933555777941757).wrapping_add(1);
+            j = ((b + 1) as f64 * ((1u64 << 31) as f64 / ((key >> 33) + 1) as f64)) as u64;
+        }
+        b as usize
+    }
+}
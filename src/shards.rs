@@ -0,0 +1,275 @@
+use crate::{make_hash, ConcurrentHashMap, Equivalent, HashTable, Shard, ShardLock, ShardSelector};
+use std::hash::{BuildHasher, Hash};
+
+/// A read-only handle to one shard of a [`ConcurrentHashMap`].
+///
+/// This `struct` is created by [`ConcurrentHashMap::shards`]. Its accessors take the shard's
+/// read lock briefly and never hand out guards.
+pub struct ShardRef<'a, K, V> {
+    index: usize,
+    lock: &'a ShardLock<Shard<K, V>>,
+}
+
+impl<K, V> ShardRef<'_, K, V> {
+    /// Returns the shard's index, in `0..N`.
+    pub fn index(&self) -> usize {
+        self.index
+    }
+
+    /// Returns the number of entries in the shard.
+    ///
+    /// **Locks** - Acquires a read lock on the shard.
+    pub fn len(&self) -> usize {
+        self.lock.read().len()
+    }
+
+    /// Returns `true` if the shard is empty.
+    ///
+    /// **Locks** - Acquires a read lock on the shard.
+    pub fn is_empty(&self) -> bool {
+        self.lock.read().is_empty()
+    }
+
+    /// Returns `true` if the shard is currently locked, for reading or writing. The answer may
+    /// be stale by the time it is used, so only use it as a hint.
+    pub fn is_locked(&self) -> bool {
+        self.lock.is_locked()
+    }
+
+    /// Returns `true` if the shard is currently write locked. See [`is_locked`](Self::is_locked).
+    pub fn is_locked_exclusive(&self) -> bool {
+        self.lock.is_locked_exclusive()
+    }
+}
+
+impl<K, V, S, const N: usize, P> ConcurrentHashMap<K, V, S, N, P> {
+    /// Returns a handle to each shard, in index order, for per-shard algorithms such as
+    /// background jobs that walk one shard at a time.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use sharded::ConcurrentHashMap;
+    /// use std::collections::hash_map::RandomState;
+    ///
+    /// let map: ConcurrentHashMap<_, _, RandomState, 4> = (0..100).map(|i| (i, i)).collect();
+    ///
+    /// let busiest = map.shards().max_by_key(|shard| shard.len()).unwrap();
+    /// assert!(busiest.index() < 4);
+    /// assert_eq!(map.shards().map(|shard| shard.len()).sum::<usize>(), 100);
+    /// ```
+    pub fn shards(&self) -> impl ExactSizeIterator<Item = ShardRef<'_, K, V>> + '_ {
+        self.shards
+            .iter()
+            .enumerate()
+            .map(|(index, lock)| ShardRef { index, lock })
+    }
+
+    /// Runs `f` on the table backing shard `index` under its read lock, for bulk per-shard
+    /// work such as export.
+    ///
+    /// **Locks** - Acquires a read lock on one of `N` shards for the duration of `f`.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `index >= N`.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use sharded::ConcurrentHashMap;
+    ///
+    /// let map: ConcurrentHashMap<_, _> = (0..100).map(|i| (i, i)).collect();
+    /// let total: usize = (0..map.shards().len())
+    ///     .map(|i| map.with_shard(i, |table| table.len()))
+    ///     .sum();
+    /// assert_eq!(total, 100);
+    /// ```
+    pub fn with_shard<F, R>(&self, index: usize, f: F) -> R
+    where
+        F: FnOnce(&HashTable<(K, V)>) -> R,
+    {
+        let shard = self.shards[index].read();
+        f(&shard.inner)
+    }
+
+    /// Runs `f` on the table backing shard `index` under its write lock, for bulk per-shard
+    /// work such as compaction.
+    ///
+    /// Entries must stay in the shard [`shard_index_of`](Self::shard_index_of) picks for their
+    /// key, and be stored under the hash the map's [`hasher`](Self::hasher) produces,
+    /// otherwise lookups will miss them.
+    ///
+    /// **Locks** - Acquires a write lock on one of `N` shards for the duration of `f`.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `index >= N`.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use sharded::ConcurrentHashMap;
+    /// use std::hash::BuildHasher;
+    ///
+    /// let map: ConcurrentHashMap<_, _> = (0..100).map(|i| (i, i)).collect();
+    /// for i in 0..map.shards().len() {
+    ///     map.with_shard_mut(i, |table| {
+    ///         table.retain(|(_, v)| *v % 2 == 0);
+    ///         table.shrink_to_fit(|(k, _)| map.hasher().hash_one(k));
+    ///     });
+    /// }
+    /// assert_eq!(map.shards().map(|shard| shard.len()).sum::<usize>(), 50);
+    /// ```
+    pub fn with_shard_mut<F, R>(&self, index: usize, f: F) -> R
+    where
+        F: FnOnce(&mut HashTable<(K, V)>) -> R,
+    {
+        let result = {
+            let mut shard = self.shards[index].write();
+            f(&mut shard.inner)
+        };
+        // `f` may have inserted
+        self.notifiers[index].notify();
+        result
+    }
+}
+
+impl<K, V, S: BuildHasher, const N: usize, P> ConcurrentHashMap<K, V, S, N, P> {
+    /// Returns the index of the shard that holds, or would hold, `key`.
+    ///
+    /// Keys on the same shard share a lock, so locking shards in ascending order of this index
+    /// is deadlock free, the same order the map's own multi-shard operations use.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use sharded::ConcurrentHashMap;
+    ///
+    /// let map = ConcurrentHashMap::new();
+    /// map.insert("a", 1);
+    ///
+    /// let i = map.shard_index_of(&"a");
+    /// assert_eq!(map.shards().nth(i).unwrap().len(), 1);
+    /// ```
+    pub fn shard_index_of<Q>(&self, key: &Q) -> usize
+    where
+        Q: Hash + Equivalent<K> + ?Sized,
+        P: ShardSelector<Q>,
+    {
+        self.shard_for(make_hash::<Q, _>(&self.hash_builder, key), key)
+    }
+
+    /// Removes and returns every entry of shard `index`, e.g. to hand ownership of the shard to
+    /// another process, which restores it with [`import_shard`](Self::import_shard).
+    ///
+    /// Shard indices only agree between processes whose maps route keys identically: the same
+    /// `N` and selector, and a hasher with fixed keys such as `DeterministicState` rather than
+    /// a randomly keyed one, or a selector like [`ModuloSelector`](crate::ModuloSelector) that
+    /// ignores the hash.
+    ///
+    /// **Locks** - Acquires a write lock on one of `N` shards.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `index >= N`.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use sharded::{ConcurrentHashMap, ModuloSelector};
+    ///
+    /// let build = || ConcurrentHashMap::builder().shards::<4>().selector(ModuloSelector).build();
+    /// let (a, b) = (build(), build());
+    /// a.insert_batch((0..100u32).map(|i| (i, i)));
+    ///
+    /// let moved = a.export_shard(1);
+    /// assert_eq!(moved.len(), 25);
+    /// b.import_shard(1, moved);
+    /// assert_eq!(*b.get(&13).unwrap(), 13);
+    /// assert!(a.get(&13).is_none());
+    /// ```
+    pub fn export_shard(&self, index: usize) -> Vec<(K, V)> {
+        let mut shard = self.shards[index].write();
+        let entries = shard.inner.drain().collect();
+        shard.inner.shrink_to_fit(|_| 0);
+        entries
+    }
+
+    /// Inserts entries exported from shard `index` of another map, replacing the values of
+    /// keys already present. See [`export_shard`](Self::export_shard).
+    ///
+    /// **Locks** - Acquires a write lock on one of `N` shards.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `index >= N`, or if any key belongs to a different shard of this map, in which
+    /// case nothing is inserted.
+    pub fn import_shard<I>(&self, index: usize, entries: I)
+    where
+        K: Hash + Eq,
+        P: ShardSelector<K>,
+        I: IntoIterator<Item = (K, V)>,
+    {
+        let entries: Vec<(u64, K, V)> = entries
+            .into_iter()
+            .map(|(k, v)| (make_hash::<K, _>(&self.hash_builder, &k), k, v))
+            .collect();
+
+        if entries
+            .iter()
+            .any(|(hash, k, _)| self.shard_for(*hash, k) != index)
+        {
+            panic!("entry does not belong to shard {}", index);
+        }
+
+        let mut shard = self.shards[index].write();
+        shard.reserve(entries.len(), &self.hash_builder);
+        for (hash, k, v) in entries {
+            shard.insert(hash, k, v, &self.hash_builder);
+        }
+        drop(shard);
+        self.notifiers[index].notify();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::ConcurrentHashMap;
+    use std::collections::hash_map::RandomState;
+    use std::panic::AssertUnwindSafe;
+
+    #[test]
+    fn test_shard_index_of_agrees_with_shards() {
+        let map: ConcurrentHashMap<u32, u32, RandomState, 8> = ConcurrentHashMap::default();
+
+        for key in 0..200 {
+            let i = map.shard_index_of(&key);
+            let before = map.shards().nth(i).unwrap().len();
+            map.insert(key, key);
+            assert_eq!(map.shards().nth(i).unwrap().len(), before + 1);
+        }
+        assert_eq!(map.shards().len(), 8);
+        assert!(map.shards().all(|shard| !shard.is_empty()));
+    }
+
+    #[test]
+    fn test_import_rejects_foreign_keys() {
+        let map: ConcurrentHashMap<u32, u32, RandomState, 8> = (0..100).map(|i| (i, i)).collect();
+        let i = map.shard_index_of(&7);
+        let exported = map.export_shard(i);
+        assert!(exported.iter().all(|(k, _)| map.shard_index_of(k) == i));
+        assert_eq!(map.with_shard(i, |table| table.capacity()), 0);
+
+        let other = (i + 1) % 8;
+        let before = map.shards().nth(other).unwrap().len();
+        let result = std::panic::catch_unwind(AssertUnwindSafe(|| {
+            map.import_shard(other, exported.clone())
+        }));
+        assert!(result.is_err());
+        assert_eq!(map.shards().nth(other).unwrap().len(), before);
+
+        map.import_shard(i, exported);
+        assert_eq!(*map.get(&7).unwrap(), 7);
+    }
+}
@@ -0,0 +1,152 @@
+use crate::{ConcurrentHashMap, DEFAULT_SHARD_COUNT};
+use std::collections::hash_map::RandomState;
+use std::hash::{BuildHasher, Hash};
+use std::sync::{Arc, OnceLock};
+
+/// Coalesces concurrent calls for the same key, so an expensive operation runs once while
+/// the other callers wait for its result.
+///
+/// Unlike [`ShardedOnceMap`](crate::ShardedOnceMap) results aren't kept: a call's entry is
+/// removed right after it finishes, and later calls for the key run again. Put it in front
+/// of a cache load to make the cache stampede proof.
+///
+/// # Examples
+///
+/// ```
+/// use sharded::{ConcurrentHashMap, KeyedSingleflight};
+///
+/// let cache = ConcurrentHashMap::new();
+/// let loads = KeyedSingleflight::new();
+///
+/// let load = |key: u32| match cache.get_cloned(&key) {
+///     Some(value) => value,
+///     None => loads.run(key, || {
+///         // only one caller per key queries the database at a time
+///         let value = key * 2;
+///         cache.insert(key, value);
+///         value
+///     }),
+/// };
+/// assert_eq!(load(21), 42);
+/// ```
+pub struct KeyedSingleflight<K, V, S = RandomState, const N: usize = DEFAULT_SHARD_COUNT> {
+    calls: ConcurrentHashMap<K, Arc<OnceLock<V>>, S, N>,
+}
+
+impl<K, V> KeyedSingleflight<K, V, RandomState, DEFAULT_SHARD_COUNT> {
+    /// Creates an empty `KeyedSingleflight`.
+    #[must_use]
+    pub fn new() -> KeyedSingleflight<K, V, RandomState> {
+        Default::default()
+    }
+}
+
+impl<K, V, S, const N: usize> Default for KeyedSingleflight<K, V, S, N>
+where
+    S: Default + BuildHasher,
+{
+    fn default() -> Self {
+        KeyedSingleflight {
+            calls: ConcurrentHashMap::default(),
+        }
+    }
+}
+
+impl<K, V, S: BuildHasher, const N: usize> KeyedSingleflight<K, V, S, N> {
+    /// Runs `f` for `key` unless a call for the key is already in flight, in which case this
+    /// blocks until that call finishes and returns a clone of its result.
+    ///
+    /// A caller arriving just as a call finishes, before its entry is removed, also gets that
+    /// call's result instead of running `f`. The shard lock is not held while `f` runs. If `f`
+    /// panics, the panic propagates to its caller and one of the waiting callers, or the next
+    /// caller, runs its own `f` instead.
+    ///
+    /// **Locks** - Acquires a read lock on one of `N` shards, plus a write lock to start and
+    /// to finish a call.
+    pub fn run<F>(&self, key: K, f: F) -> V
+    where
+        K: Hash + Eq + Clone,
+        V: Clone,
+        F: FnOnce() -> V,
+    {
+        let call = match self.calls.get_cloned(&key) {
+            Some(call) => call,
+            None => match self.calls.try_insert(key.clone(), Arc::default()) {
+                Ok(call) => call.clone(),
+                Err(occupied) => occupied.existing.clone(),
+            },
+        };
+
+        let mut ran = false;
+        let value = call
+            .get_or_init(|| {
+                ran = true;
+                f()
+            })
+            .clone();
+
+        if ran {
+            // a later call may already have replaced ours
+            self.calls
+                .remove_if(&key, |_, current| Arc::ptr_eq(current, &call));
+        }
+        value
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::sync::Barrier;
+
+    #[test]
+    fn test_concurrent_callers_share_one_run() {
+        let flights = Arc::new(KeyedSingleflight::<u32, usize>::new());
+        let calls = Arc::new(AtomicUsize::new(0));
+        let barrier = Arc::new(Barrier::new(8));
+
+        let workers: Vec<_> = (0..8)
+            .map(|_| {
+                let (flights, calls, barrier) = (flights.clone(), calls.clone(), barrier.clone());
+                std::thread::spawn(move || {
+                    barrier.wait();
+                    flights.run(1, || {
+                        std::thread::sleep(std::time::Duration::from_millis(50));
+                        calls.fetch_add(1, Ordering::SeqCst)
+                    })
+                })
+            })
+            .collect();
+
+        let results: Vec<usize> = workers.into_iter().map(|w| w.join().unwrap()).collect();
+        assert_eq!(calls.load(Ordering::SeqCst), 1);
+        assert!(results.iter().all(|&r| r == 0));
+
+        // finished calls aren't cached
+        assert_eq!(flights.run(1, || 7), 7);
+    }
+
+    #[test]
+    fn test_waiter_runs_after_leader_panics() {
+        let flights = Arc::new(KeyedSingleflight::<u32, u32>::new());
+        let (started, leader_started) = std::sync::mpsc::channel();
+
+        let leader = {
+            let flights = flights.clone();
+            std::thread::spawn(move || {
+                flights.run(1, || {
+                    started.send(()).unwrap();
+                    std::thread::sleep(std::time::Duration::from_millis(50));
+                    panic!("load failed")
+                })
+            })
+        };
+
+        // joins the leader's call, then retries once it panics
+        leader_started.recv().unwrap();
+        assert_eq!(flights.run(1, || 7), 7);
+        assert!(leader.join().is_err());
+        assert_eq!(flights.run(1, || 8), 8);
+    }
+}
@@ -0,0 +1,120 @@
+use crate::{make_hash, select_shard, CachePadded, Equivalent, HashSelector, DEFAULT_SHARD_COUNT};
+use parking_lot::{RwLock, RwLockReadGuard, RwLockWriteGuard};
+use std::collections::hash_map::RandomState;
+use std::hash::{BuildHasher, Hash};
+use std::marker::PhantomData;
+
+/// A keyed lock without storage: keys are mapped onto `N` padded locks the same way a
+/// [`ConcurrentHashMap`](crate::ConcurrentHashMap) maps them onto shards.
+///
+/// Useful to guard external per-key resources such as files or connections. Distinct keys
+/// may share a stripe, so holding two stripes at once must follow
+/// [`stripe_of`](Self::stripe_of) order to avoid deadlocks.
+///
+/// # Examples
+///
+/// ```
+/// use sharded::StripedLock;
+///
+/// let files: StripedLock<str> = StripedLock::new();
+/// {
+///     let _guard = files.write("a.log");
+///     // append to a.log
+/// }
+/// let _a = files.read("a.log");
+/// let _b = files.read("b.log");
+/// ```
+pub struct StripedLock<K: ?Sized, S = RandomState, const N: usize = DEFAULT_SHARD_COUNT> {
+    hash_builder: S,
+    stripes: [CachePadded<RwLock<()>>; N],
+    _key: PhantomData<fn(&K)>,
+}
+
+impl<K: ?Sized> StripedLock<K, RandomState, DEFAULT_SHARD_COUNT> {
+    /// Creates a `StripedLock` with the default hasher and stripe count.
+    #[must_use]
+    pub fn new() -> StripedLock<K, RandomState> {
+        Default::default()
+    }
+}
+
+impl<K: ?Sized, S: Default, const N: usize> Default for StripedLock<K, S, N> {
+    fn default() -> Self {
+        StripedLock::with_hasher(S::default())
+    }
+}
+
+impl<K: ?Sized, S, const N: usize> StripedLock<K, S, N> {
+    // same check as the map's constructors
+    const NONZERO_STRIPES: () = assert!(N > 0, "number of stripes must be > 0");
+
+    /// Creates a `StripedLock` using `hash_builder` to hash the keys.
+    pub fn with_hasher(hash_builder: S) -> Self {
+        let () = Self::NONZERO_STRIPES;
+
+        StripedLock {
+            hash_builder,
+            stripes: std::array::from_fn(|_| CachePadded::new(RwLock::new(()))),
+            _key: PhantomData,
+        }
+    }
+}
+
+impl<K: ?Sized, S: BuildHasher, const N: usize> StripedLock<K, S, N> {
+    /// Returns the index of the stripe guarding `key`, in `0..N`.
+    pub fn stripe_of<Q>(&self, key: &Q) -> usize
+    where
+        Q: Hash + Equivalent<K> + ?Sized,
+    {
+        let hash = make_hash::<Q, _>(&self.hash_builder, key);
+        select_shard(&HashSelector, hash, key, N)
+    }
+
+    /// Acquires the shared lock guarding `key`.
+    pub fn read<Q>(&self, key: &Q) -> RwLockReadGuard<'_, ()>
+    where
+        Q: Hash + Equivalent<K> + ?Sized,
+    {
+        self.stripes[self.stripe_of(key)].read()
+    }
+
+    /// Acquires the exclusive lock guarding `key`.
+    pub fn write<Q>(&self, key: &Q) -> RwLockWriteGuard<'_, ()>
+    where
+        Q: Hash + Equivalent<K> + ?Sized,
+    {
+        self.stripes[self.stripe_of(key)].write()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::Arc;
+
+    #[test]
+    fn test_write_excludes_same_key_only() {
+        let locks: Arc<StripedLock<str, RandomState, 8>> = Arc::default();
+        let (a, b) = (
+            "a",
+            (0..)
+                .map(|i| i.to_string())
+                .find(|k| locks.stripe_of(k.as_str()) != locks.stripe_of("a"))
+                .unwrap(),
+        );
+
+        let guard = locks.write(a);
+        let other = {
+            let (locks, b) = (locks.clone(), b.clone());
+            std::thread::spawn(move || drop(locks.write(b.as_str())))
+        };
+        other.join().unwrap();
+        assert!(locks.stripes[locks.stripe_of(a)].try_read().is_none());
+        drop(guard);
+
+        let _readers = (locks.read(a), locks.read(a));
+        assert!(locks.stripes[locks.stripe_of(b.as_str())]
+            .try_write()
+            .is_some());
+    }
+}
@@ -0,0 +1,182 @@
+use crate::ConcurrentHashMap;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::Duration;
+
+const BUCKETS: usize = 32;
+
+/// A histogram of lock durations with power of two buckets, see
+/// [`ConcurrentHashMap::contention_stats`].
+///
+/// Bucket `i` counts durations of less than `2^i` nanoseconds that didn't fit a lower bucket,
+/// and the last bucket also takes everything longer.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub struct LockHistogram {
+    buckets: [u64; BUCKETS],
+}
+
+impl LockHistogram {
+    /// Returns the count of each bucket.
+    pub fn buckets(&self) -> &[u64; BUCKETS] {
+        &self.buckets
+    }
+
+    /// Returns the number of recorded durations.
+    pub fn count(&self) -> u64 {
+        self.buckets.iter().sum()
+    }
+
+    /// Returns an upper bound for the `q`th quantile, e.g. `0.99` for the 99th percentile, or
+    /// `None` if nothing was recorded. Bounds are capped at the last bucket's, about 2.1s.
+    pub fn quantile(&self, q: f64) -> Option<Duration> {
+        let count = self.count();
+        if count == 0 {
+            return None;
+        }
+
+        let rank = ((count as f64 * q.clamp(0.0, 1.0)).ceil() as u64).max(1);
+        let mut seen = 0;
+        let bucket = self.buckets.iter().position(|&n| {
+            seen += n;
+            seen >= rank
+        })?;
+        Some(Duration::from_nanos(1 << bucket))
+    }
+}
+
+/// Lock wait and hold times of one shard, see [`ConcurrentHashMap::contention_stats`].
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+#[non_exhaustive]
+pub struct ContentionStats {
+    /// Time spent waiting for the shard's lock, for every blocking acquisition
+    pub wait: LockHistogram,
+    /// Time the lock was held, for every acquisition: single key operations like `insert`,
+    /// callbacks like `for_each`, views, transactions, entries, and guards returned to the
+    /// caller, like the one from `get`, until they are dropped.
+    pub hold: LockHistogram,
+}
+
+/// The live counterpart of [`LockHistogram`], shared by all threads.
+#[derive(Default)]
+pub(crate) struct AtomicHistogram {
+    buckets: [AtomicU64; BUCKETS],
+}
+
+impl AtomicHistogram {
+    #[inline]
+    pub(crate) fn record(&self, duration: Duration) {
+        let nanos = u64::try_from(duration.as_nanos()).unwrap_or(u64::MAX);
+        let bucket = (u64::BITS - nanos.leading_zeros()) as usize;
+        self.buckets[bucket.min(BUCKETS - 1)].fetch_add(1, Ordering::Relaxed);
+    }
+
+    fn snapshot(&self) -> LockHistogram {
+        LockHistogram {
+            buckets: std::array::from_fn(|i| self.buckets[i].load(Ordering::Relaxed)),
+        }
+    }
+}
+
+/// Wait and hold histograms of one shard lock.
+#[derive(Default)]
+pub(crate) struct Timing {
+    pub(crate) wait: AtomicHistogram,
+    pub(crate) hold: AtomicHistogram,
+}
+
+impl<K, V, S, const N: usize, P> ConcurrentHashMap<K, V, S, N, P> {
+    /// Returns the lock wait and hold time histograms of each shard, in shard index order,
+    /// since the map was created. Useful to find hot shards and to compare shard counts.
+    ///
+    /// Only available with the `lock-timing` feature, which adds a clock read to every lock
+    /// acquisition.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use sharded::ConcurrentHashMap;
+    /// use std::collections::hash_map::RandomState;
+    ///
+    /// let map: ConcurrentHashMap<_, _, RandomState, 4> = ConcurrentHashMap::default();
+    /// for i in 0..100 {
+    ///     map.insert(i, i);
+    /// }
+    /// map.for_each(|_, _| {});
+    ///
+    /// let stats = map.contention_stats();
+    /// assert_eq!(stats.iter().map(|s| s.wait.count()).sum::<u64>(), 104);
+    /// assert_eq!(stats.iter().map(|s| s.hold.count()).sum::<u64>(), 104);
+    /// let worst = stats.iter().filter_map(|s| s.wait.quantile(0.99)).max();
+    /// assert!(worst.is_some());
+    /// ```
+    pub fn contention_stats(&self) -> [ContentionStats; N] {
+        std::array::from_fn(|i| ContentionStats {
+            wait: self.shards[i].timing().wait.snapshot(),
+            hold: self.shards[i].timing().hold.snapshot(),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::hash_map::RandomState;
+
+    #[test]
+    fn test_histogram_buckets_and_quantiles() {
+        let live = AtomicHistogram::default();
+        live.record(Duration::ZERO);
+        live.record(Duration::from_nanos(3));
+        live.record(Duration::from_nanos(1000));
+        live.record(Duration::from_secs(1000));
+
+        let histogram = live.snapshot();
+        assert_eq!(histogram.count(), 4);
+        assert_eq!(histogram.buckets()[0], 1);
+        assert_eq!(histogram.buckets()[2], 1);
+        assert_eq!(histogram.buckets()[10], 1);
+        assert_eq!(histogram.buckets()[BUCKETS - 1], 1);
+        assert_eq!(histogram.quantile(0.5), Some(Duration::from_nanos(4)));
+        assert_eq!(histogram.quantile(0.75), Some(Duration::from_nanos(1024)));
+        assert_eq!(
+            LockHistogram {
+                buckets: [0; BUCKETS]
+            }
+            .quantile(0.5),
+            None
+        );
+    }
+
+    #[test]
+    fn test_hold_time_covers_callbacks() {
+        let map: ConcurrentHashMap<u32, u32, RandomState, 1> = ConcurrentHashMap::default();
+        map.insert(1, 1);
+        map.for_each(|_, _| std::thread::sleep(Duration::from_millis(2)));
+
+        let [stats] = map.contention_stats();
+        assert_eq!(stats.wait.count(), 2);
+        assert_eq!(stats.hold.count(), 2);
+        assert!(stats.hold.quantile(1.0).unwrap() >= Duration::from_millis(2));
+    }
+
+    #[test]
+    fn test_hold_time_covers_single_key_operations() {
+        let map: ConcurrentHashMap<u32, u32, RandomState, 1> = ConcurrentHashMap::default();
+        let holds = || map.contention_stats()[0].hold.count();
+
+        map.insert(1, 1);
+        assert_eq!(holds(), 1);
+        assert_eq!(map.get_cloned(&1), Some(1));
+        assert_eq!(holds(), 2);
+
+        // a returned guard is timed once it is dropped
+        let guard = map.get(&1).unwrap();
+        assert_eq!(holds(), 2);
+        drop(guard);
+        assert_eq!(holds(), 3);
+
+        assert_eq!(map.remove(&1), Some(1));
+        assert_eq!(holds(), 4);
+    }
+}
@@ -0,0 +1,189 @@
+use crate::{
+    make_hash, select_shard, ConcurrentHashMap, Equivalent, Notifier, Shard, ShardSelector,
+    ShardWriteGuard,
+};
+use std::hash::{BuildHasher, Hash};
+
+/// Mutable access to a fixed set of keys while their shards are write locked.
+///
+/// This `struct` is created by [`ConcurrentHashMap::transact`]. Only keys passed to `transact`
+/// are guaranteed to be accessible, touching any other key panics unless it happens to share a
+/// shard with one of them.
+pub struct Transaction<'a, K, V, S, const N: usize, P> {
+    hash_builder: &'a S,
+    selector: &'a P,
+    notifiers: &'a [Notifier; N],
+    // sorted by shard index, deduplicated
+    shards: Vec<(usize, ShardWriteGuard<'a, K, V>)>,
+}
+
+impl<K, V, S: BuildHasher, const N: usize, P> ConcurrentHashMap<K, V, S, N, P> {
+    /// Write locks every shard holding one of `keys` and runs `f` with mutable access to those
+    /// entries, so updates across several keys happen atomically.
+    ///
+    /// Shards are locked in ascending index order, so concurrent transactions cannot deadlock.
+    ///
+    /// **Locks** - Acquires a write lock on each shard holding one of the keys, for the duration
+    /// of `f`.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use sharded::ConcurrentHashMap;
+    ///
+    /// let accounts = ConcurrentHashMap::new();
+    /// accounts.insert("alice", 100);
+    /// accounts.insert("bob", 20);
+    ///
+    /// let moved = accounts.transact(&[&"alice", &"bob"], |tx| {
+    ///     let amount = 30;
+    ///     if *tx.get(&"alice")? < amount {
+    ///         return None;
+    ///     }
+    ///     *tx.get_mut(&"alice")? -= amount;
+    ///     *tx.get_mut(&"bob")? += amount;
+    ///     Some(amount)
+    /// });
+    ///
+    /// assert_eq!(moved, Some(30));
+    /// assert_eq!(*accounts.get(&"bob").unwrap(), 50);
+    /// ```
+    pub fn transact<Q, F, R>(&self, keys: &[&Q], f: F) -> R
+    where
+        Q: Hash + Equivalent<K> + ?Sized,
+        P: ShardSelector<Q>,
+        F: FnOnce(&mut Transaction<'_, K, V, S, N, P>) -> R,
+    {
+        let mut indices: Vec<usize> = keys
+            .iter()
+            .map(|key| self.shard_for(make_hash::<Q, _>(&self.hash_builder, *key), *key))
+            .collect();
+        indices.sort_unstable();
+        indices.dedup();
+
+        let shards: Vec<_> = indices
+            .into_iter()
+            .map(|i| (i, self.shards[i].write()))
+            .collect();
+
+        f(&mut Transaction {
+            hash_builder: &self.hash_builder,
+            selector: &self.selector,
+            notifiers: &self.notifiers,
+            shards,
+        })
+    }
+}
+
+impl<K, V, S: BuildHasher, const N: usize, P> Transaction<'_, K, V, S, N, P> {
+    /// Returns a reference to the value corresponding to the key.
+    pub fn get<Q>(&self, key: &Q) -> Option<&V>
+    where
+        Q: Hash + Equivalent<K> + ?Sized,
+        P: ShardSelector<Q>,
+    {
+        let hash = make_hash::<Q, _>(self.hash_builder, key);
+        self.shard(hash, key).get(hash, key)
+    }
+
+    /// Returns a mutable reference to the value corresponding to the key.
+    pub fn get_mut<Q>(&mut self, key: &Q) -> Option<&mut V>
+    where
+        Q: Hash + Equivalent<K> + ?Sized,
+        P: ShardSelector<Q>,
+    {
+        let hash = make_hash::<Q, _>(self.hash_builder, key);
+        self.shard_mut(hash, key).get_mut(hash, key)
+    }
+
+    /// Inserts a key value pair, returning the previous value if there was one.
+    pub fn insert(&mut self, k: K, v: V) -> Option<V>
+    where
+        K: Hash + Eq,
+        P: ShardSelector<K>,
+    {
+        let hash = make_hash::<K, _>(self.hash_builder, &k);
+        let i = select_shard(self.selector, hash, &k, N);
+        let hash_builder = self.hash_builder;
+        let previous = self.shard_mut(hash, &k).insert(hash, k, v, hash_builder);
+        self.notifiers[i].notify();
+        previous
+    }
+
+    /// Removes a key, returning its value if it was present.
+    pub fn remove<Q>(&mut self, key: &Q) -> Option<V>
+    where
+        Q: Hash + Equivalent<K> + ?Sized,
+        P: ShardSelector<Q>,
+    {
+        let hash = make_hash::<Q, _>(self.hash_builder, key);
+        self.shard_mut(hash, key).remove(hash, key)
+    }
+
+    fn position<Q>(&self, hash: u64, key: &Q) -> usize
+    where
+        Q: ?Sized,
+        P: ShardSelector<Q>,
+    {
+        let i = select_shard(self.selector, hash, key, N);
+        match self.shards.binary_search_by_key(&i, |(index, _)| *index) {
+            Ok(position) => position,
+            Err(_) => panic!("key was not part of the transaction"),
+        }
+    }
+
+    fn shard<Q>(&self, hash: u64, key: &Q) -> &Shard<K, V>
+    where
+        Q: ?Sized,
+        P: ShardSelector<Q>,
+    {
+        &self.shards[self.position(hash, key)].1
+    }
+
+    fn shard_mut<Q>(&mut self, hash: u64, key: &Q) -> &mut Shard<K, V>
+    where
+        Q: ?Sized,
+        P: ShardSelector<Q>,
+    {
+        let position = self.position(hash, key);
+        &mut self.shards[position].1
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::ConcurrentHashMap;
+    use std::collections::hash_map::RandomState;
+    use std::sync::Arc;
+
+    #[test]
+    fn test_concurrent_transfers_preserve_total() {
+        let map: Arc<ConcurrentHashMap<u32, i64, RandomState, 8>> =
+            Arc::new(ConcurrentHashMap::default());
+        for i in 0..16 {
+            map.insert(i, 100);
+        }
+
+        let workers: Vec<_> = (0..4)
+            .map(|t| {
+                let map = map.clone();
+                std::thread::spawn(move || {
+                    for n in 0..1000u32 {
+                        let (from, to) = ((n + t) % 16, (n * 7 + t) % 16);
+                        map.transact(&[&from, &to], |tx| {
+                            *tx.get_mut(&from).unwrap() -= 1;
+                            *tx.get_mut(&to).unwrap() += 1;
+                        });
+                    }
+                })
+            })
+            .collect();
+
+        for worker in workers {
+            worker.join().unwrap();
+        }
+
+        let total: i64 = (0..16).map(|i| *map.get(&i).unwrap()).sum();
+        assert_eq!(total, 1600);
+    }
+}
@@ -0,0 +1,180 @@
+use crate::{make_hash, ConcurrentHashMap, Equivalent, ReadGuard, ShardSelector};
+use std::fmt;
+use std::hash::{BuildHasher, Hash};
+use std::time::Duration;
+
+/// The error returned by the `try_` lookups when the shard is locked by a writer, as opposed
+/// to `Ok(None)` when the key is absent.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct WouldBlock;
+
+impl fmt::Display for WouldBlock {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("shard is locked")
+    }
+}
+
+impl std::error::Error for WouldBlock {}
+
+impl<K, V, S: BuildHasher, const N: usize, P> ConcurrentHashMap<K, V, S, N, P> {
+    /// Like [`get`](Self::get), but returns [`WouldBlock`] instead of blocking if the shard is
+    /// write locked.
+    ///
+    /// **Locks** - Tries to acquire a read lock on one of `N` shards for the returned guard.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use sharded::ConcurrentHashMap;
+    ///
+    /// let map = ConcurrentHashMap::new();
+    /// map.insert(1, "a");
+    /// assert_eq!(*map.try_get(&1).unwrap().unwrap(), "a");
+    /// assert!(map.try_get(&2).unwrap().is_none());
+    ///
+    /// let writer = map.get_mut(&1).unwrap();
+    /// assert!(map.try_get(&1).is_err());
+    /// drop(writer);
+    /// ```
+    pub fn try_get<Q>(&self, key: &Q) -> Result<Option<ReadGuard<'_, V>>, WouldBlock>
+    where
+        Q: Hash + Equivalent<K> + ?Sized,
+        P: ShardSelector<Q>,
+    {
+        let hash = make_hash::<Q, _>(&self.hash_builder, key);
+        let shard = self.shards[self.shard_for(hash, key)]
+            .try_read()
+            .ok_or(WouldBlock)?;
+        Ok(shard.try_map(|shard| shard.get(hash, key)))
+    }
+
+    /// Like [`get`](Self::get), but gives up and returns [`WouldBlock`] if the shard's read lock
+    /// can't be acquired within `timeout`.
+    ///
+    /// **Locks** - Tries to acquire a read lock on one of `N` shards for the returned guard,
+    /// waiting at most `timeout`.
+    pub fn try_get_for<Q>(
+        &self,
+        key: &Q,
+        timeout: Duration,
+    ) -> Result<Option<ReadGuard<'_, V>>, WouldBlock>
+    where
+        Q: Hash + Equivalent<K> + ?Sized,
+        P: ShardSelector<Q>,
+    {
+        let hash = make_hash::<Q, _>(&self.hash_builder, key);
+        let shard = self.shards[self.shard_for(hash, key)]
+            .try_read_for(timeout)
+            .ok_or(WouldBlock)?;
+        Ok(shard.try_map(|shard| shard.get(hash, key)))
+    }
+
+    /// Returns `true` if the shard holding `key` is currently locked, for reading or writing,
+    /// e.g. so load shedding can route around contended keys. The answer may be stale by the
+    /// time it is used, so only use it as a hint. [`shards`](Self::shards) has a per-shard
+    /// variant.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use sharded::ConcurrentHashMap;
+    ///
+    /// let map = ConcurrentHashMap::new();
+    /// map.insert(1, "a");
+    /// assert!(!map.is_locked(&1));
+    ///
+    /// let reader = map.get(&1).unwrap();
+    /// assert!(map.is_locked(&1));
+    /// assert!(!map.is_locked_exclusive(&1));
+    /// drop(reader);
+    /// ```
+    pub fn is_locked<Q>(&self, key: &Q) -> bool
+    where
+        Q: Hash + Equivalent<K> + ?Sized,
+        P: ShardSelector<Q>,
+    {
+        let hash = make_hash::<Q, _>(&self.hash_builder, key);
+        self.shards[self.shard_for(hash, key)].is_locked()
+    }
+
+    /// Returns `true` if the shard holding `key` is currently write locked. See
+    /// [`is_locked`](Self::is_locked).
+    pub fn is_locked_exclusive<Q>(&self, key: &Q) -> bool
+    where
+        Q: Hash + Equivalent<K> + ?Sized,
+        P: ShardSelector<Q>,
+    {
+        let hash = make_hash::<Q, _>(&self.hash_builder, key);
+        self.shards[self.shard_for(hash, key)].is_locked_exclusive()
+    }
+
+    /// Like [`insert`](Self::insert), but gives up if the shard's write lock can't be acquired
+    /// within `timeout`, handing the pair back.
+    ///
+    /// **Locks** - Tries to acquire a write lock on one of `N` shards, waiting at most
+    /// `timeout`.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use sharded::ConcurrentHashMap;
+    /// use std::time::Duration;
+    ///
+    /// let map = ConcurrentHashMap::new();
+    /// assert_eq!(map.try_insert_for(1, "a", Duration::from_millis(10)), Ok(None));
+    ///
+    /// let reader = map.get(&1).unwrap();
+    /// assert_eq!(map.try_insert_for(1, "b", Duration::from_millis(10)), Err((1, "b")));
+    /// drop(reader);
+    /// ```
+    pub fn try_insert_for(&self, k: K, v: V, timeout: Duration) -> Result<Option<V>, (K, V)>
+    where
+        K: Hash + Eq,
+        P: ShardSelector<K>,
+    {
+        let hash = make_hash::<K, _>(&self.hash_builder, &k);
+        let i = self.shard_for(hash, &k);
+
+        let mut shard = match self.shards[i].try_write_for(timeout) {
+            Some(shard) => shard,
+            None => return Err((k, v)),
+        };
+        let previous = shard.insert(hash, k, v, &self.hash_builder);
+        self.notifiers[i].notify();
+        Ok(previous)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{ConcurrentHashMap, WouldBlock};
+    use std::collections::hash_map::RandomState;
+    use std::time::Duration;
+
+    #[test]
+    fn test_timed_ops_give_up_on_held_shard() {
+        let map: ConcurrentHashMap<u32, u32, RandomState, 1> = ConcurrentHashMap::default();
+        let timeout = Duration::from_millis(5);
+        map.insert(1, 1);
+
+        let writer = map.get_mut(&1).unwrap();
+        assert!(map.is_locked_exclusive(&2));
+        assert!(map.shards().all(|shard| shard.is_locked_exclusive()));
+        assert_eq!(map.try_get(&1).err(), Some(WouldBlock));
+        assert_eq!(map.try_get_for(&2, timeout).err(), Some(WouldBlock));
+        drop(writer);
+
+        let reader = map.try_get_for(&1, timeout).unwrap().unwrap();
+        assert_eq!(*reader, 1);
+        assert_eq!(map.try_insert_for(2, 2, timeout), Err((2, 2)));
+        assert!(map
+            .shards()
+            .all(|shard| shard.is_locked() && !shard.is_locked_exclusive()));
+        drop(reader);
+
+        assert!(!map.is_locked(&1));
+        assert_eq!(map.try_insert_for(1, 3, timeout), Ok(Some(1)));
+        assert!(map.try_get(&2).unwrap().is_none());
+        assert_eq!(*map.try_get(&1).unwrap().unwrap(), 3);
+    }
+}
@@ -0,0 +1,143 @@
+use crate::lock::Locked;
+use crate::{make_hash, ConcurrentHashMap, Notifier, Shard, ShardSelector, WriteGuard};
+use parking_lot::RwLockUpgradableReadGuard;
+use std::hash::{BuildHasher, Hash};
+
+/// An upgradable read lock on the shard holding one key.
+///
+/// This `struct` is created by [`ConcurrentHashMap::read_upgradable`]. Plain readers of the shard
+/// proceed while it is held, but writers and other upgradable readers wait, so nothing can
+/// change the key between the check and the upgrade.
+pub struct UpgradableReadGuard<'a, K, V, S> {
+    shard: Locked<'a, RwLockUpgradableReadGuard<'a, Shard<K, V>>>,
+    hash_builder: &'a S,
+    notifier: &'a Notifier,
+    hash: u64,
+    key: K,
+}
+
+impl<K, V, S: BuildHasher, const N: usize, P> ConcurrentHashMap<K, V, S, N, P> {
+    /// Takes an upgradable read lock on the shard holding `key`, for check-then-insert without
+    /// dropping the read lock and racing for the write lock.
+    ///
+    /// **Locks** - Acquires an upgradable read lock on one of `N` shards for the returned guard.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use sharded::ConcurrentHashMap;
+    ///
+    /// let cache = ConcurrentHashMap::new();
+    ///
+    /// let guard = cache.read_upgradable("config");
+    /// if guard.get().is_none() {
+    ///     *guard.or_insert_with(|| 0) += 1;
+    /// }
+    /// assert_eq!(*cache.get("config").unwrap(), 1);
+    /// ```
+    pub fn read_upgradable(&self, key: K) -> UpgradableReadGuard<'_, K, V, S>
+    where
+        K: Hash + Eq,
+        P: ShardSelector<K>,
+    {
+        let hash = make_hash::<K, _>(&self.hash_builder, &key);
+        let i = self.shard_for(hash, &key);
+
+        UpgradableReadGuard {
+            shard: self.shards[i].upgradable_read(),
+            hash_builder: &self.hash_builder,
+            notifier: &self.notifiers[i],
+            hash,
+            key,
+        }
+    }
+}
+
+impl<'a, K: Hash + Eq, V, S: BuildHasher> UpgradableReadGuard<'a, K, V, S> {
+    /// Returns the key the guard was created for.
+    pub fn key(&self) -> &K {
+        &self.key
+    }
+
+    /// Returns a reference to the value, if the key is present.
+    pub fn get(&self) -> Option<&V> {
+        self.shard.get(self.hash, &self.key)
+    }
+
+    /// Atomically upgrades to a write lock and returns the value, or releases the lock and
+    /// returns `None` if the key is absent.
+    pub fn upgrade(self) -> Option<WriteGuard<'a, V>> {
+        let (hash, key) = (self.hash, self.key);
+        self.shard
+            .upgrade()
+            .try_map(|shard| shard.get_mut(hash, &key))
+            .ok()
+    }
+
+    /// Atomically upgrades to a write lock, inserting the result of `f` if the key is absent,
+    /// and returns the value.
+    pub fn or_insert_with<F>(self, f: F) -> WriteGuard<'a, V>
+    where
+        F: FnOnce() -> V,
+    {
+        let (hash, key, hash_builder) = (self.hash, self.key, self.hash_builder);
+        let mut inserted = false;
+        let value = self.shard.upgrade().map(|shard| {
+            let f = || {
+                inserted = true;
+                f()
+            };
+            shard.get_or_insert_with(hash, key, f, hash_builder)
+        });
+        if inserted {
+            self.notifier.notify();
+        }
+        value
+    }
+
+    /// Atomically upgrades to a write lock and inserts `value`, returning the previous value if
+    /// there was one.
+    pub fn insert(self, value: V) -> Option<V> {
+        let mut shard = self.shard.upgrade();
+        let previous = shard.insert(self.hash, self.key, value, self.hash_builder);
+        self.notifier.notify();
+        previous
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::ConcurrentHashMap;
+    use std::collections::hash_map::RandomState;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::sync::Arc;
+
+    #[test]
+    fn test_check_then_insert_runs_once() {
+        let map: Arc<ConcurrentHashMap<u32, u32, RandomState, 4>> =
+            Arc::new(ConcurrentHashMap::default());
+        let calls = Arc::new(AtomicUsize::new(0));
+
+        let workers: Vec<_> = (0..8)
+            .map(|_| {
+                let (map, calls) = (map.clone(), calls.clone());
+                std::thread::spawn(move || {
+                    let guard = map.read_upgradable(1);
+                    if guard.get().is_none() {
+                        let _ =
+                            guard.or_insert_with(|| calls.fetch_add(1, Ordering::SeqCst) as u32);
+                    }
+                })
+            })
+            .collect();
+        for worker in workers {
+            worker.join().unwrap();
+        }
+        assert_eq!(calls.load(Ordering::SeqCst), 1);
+
+        assert!(map.read_upgradable(2).upgrade().is_none());
+        assert_eq!(map.read_upgradable(1).insert(5), Some(0));
+        *map.read_upgradable(1).upgrade().unwrap() += 1;
+        assert_eq!(*map.get(&1).unwrap(), 6);
+    }
+}
@@ -0,0 +1,117 @@
+use crate::{
+    make_hash, select_shard, ConcurrentHashMap, Equivalent, ShardReadGuard, ShardSelector,
+};
+use std::hash::{BuildHasher, Hash};
+
+/// A consistent, read-only view of the whole map while every shard is read locked.
+///
+/// This `struct` is created by [`ConcurrentHashMap::read_all`]. Writers block until it is
+/// dropped.
+pub struct ReadView<'a, K, V, S, const N: usize, P> {
+    hash_builder: &'a S,
+    selector: &'a P,
+    shards: [ShardReadGuard<'a, K, V>; N],
+}
+
+impl<K, V, S, const N: usize, P> ConcurrentHashMap<K, V, S, N, P> {
+    /// Read locks every shard and returns a view of a point-in-time consistent state of the
+    /// map, with no torn reads across shards.
+    ///
+    /// Shards are locked in ascending index order, the same order multi-shard writers use.
+    /// The calling thread must not lock the map again while holding the view, that deadlocks,
+    /// or panics in debug builds.
+    ///
+    /// **Locks** - Acquires a read lock on all `N` shards until the view is dropped.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use sharded::ConcurrentHashMap;
+    ///
+    /// let balances = ConcurrentHashMap::from([("alice", 70), ("bob", 30)]);
+    ///
+    /// let view = balances.read_all();
+    /// assert_eq!(view.len(), 2);
+    /// assert_eq!(view.get(&"bob"), Some(&30));
+    /// assert_eq!(view.iter().map(|(_, v)| v).sum::<i32>(), 100);
+    /// ```
+    pub fn read_all(&self) -> ReadView<'_, K, V, S, N, P> {
+        ReadView {
+            hash_builder: &self.hash_builder,
+            selector: &self.selector,
+            shards: std::array::from_fn(|i| self.shards[i].read()),
+        }
+    }
+}
+
+impl<K, V, S: BuildHasher, const N: usize, P> ReadView<'_, K, V, S, N, P> {
+    /// Returns a reference to the value corresponding to the key.
+    pub fn get<Q>(&self, key: &Q) -> Option<&V>
+    where
+        Q: Hash + Equivalent<K> + ?Sized,
+        P: ShardSelector<Q>,
+    {
+        let hash = make_hash::<Q, _>(self.hash_builder, key);
+        self.shards[select_shard(self.selector, hash, key, N)].get(hash, key)
+    }
+
+    /// Returns `true` if the map contains the key.
+    pub fn contains_key<Q>(&self, key: &Q) -> bool
+    where
+        Q: Hash + Equivalent<K> + ?Sized,
+        P: ShardSelector<Q>,
+    {
+        self.get(key).is_some()
+    }
+
+    /// Returns the number of entries in the map.
+    pub fn len(&self) -> usize {
+        self.shards.iter().map(|shard| shard.len()).sum()
+    }
+
+    /// Returns `true` if the map is empty.
+    pub fn is_empty(&self) -> bool {
+        self.shards.iter().all(|shard| shard.is_empty())
+    }
+
+    /// Iterates over every entry, shard by shard.
+    pub fn iter(&self) -> impl Iterator<Item = (&K, &V)> + '_ {
+        self.shards
+            .iter()
+            .flat_map(|shard| shard.inner.iter().map(|(k, v)| (k, v)))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::ConcurrentHashMap;
+    use std::collections::hash_map::RandomState;
+    use std::sync::Arc;
+
+    #[test]
+    fn test_read_all_sees_no_torn_transfers() {
+        let map: Arc<ConcurrentHashMap<u32, i64, RandomState, 8>> =
+            Arc::new((0..16).map(|i| (i, 100)).collect());
+
+        let writer = {
+            let map = map.clone();
+            std::thread::spawn(move || {
+                for n in 0..2000u32 {
+                    let (from, to) = (n % 16, (n * 7 + 3) % 16);
+                    map.transact(&[&from, &to], |tx| {
+                        *tx.get_mut(&from).unwrap() -= 1;
+                        *tx.get_mut(&to).unwrap() += 1;
+                    });
+                }
+            })
+        };
+
+        for _ in 0..200 {
+            let view = map.read_all();
+            assert_eq!(view.len(), 16);
+            assert_eq!(view.iter().map(|(_, v)| v).sum::<i64>(), 1600);
+        }
+
+        writer.join().unwrap();
+    }
+}
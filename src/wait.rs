@@ -0,0 +1,384 @@
+use crate::{make_hash, ConcurrentHashMap, Equivalent, ReadGuard, ShardSelector};
+use parking_lot::{Condvar, Mutex};
+#[cfg(feature = "async")]
+use std::future::Future;
+use std::hash::{BuildHasher, Hash};
+#[cfg(feature = "async")]
+use std::pin::Pin;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::task::Waker;
+#[cfg(feature = "async")]
+use std::task::{Context, Poll};
+use std::time::{Duration, Instant};
+
+/// Wakes threads parked in [`ConcurrentHashMap::wait_for`] on one shard, and tasks waiting
+/// on its async variant.
+///
+/// Writers only pay for an atomic load unless someone is waiting. Waiters compare `epoch`
+/// under `wakers` before parking, so an insert that lands between a waiter's lookup and its
+/// park is never missed, and writers may notify while still holding the shard lock.
+#[derive(Default)]
+pub(crate) struct Notifier {
+    waiters: AtomicUsize,
+    epoch: AtomicUsize,
+    // keyed by registration, so each future only ever removes its own waker
+    wakers: Mutex<Vec<(usize, Waker)>>,
+    #[cfg(feature = "async")]
+    registrations: AtomicUsize,
+    cond: Condvar,
+}
+
+impl Notifier {
+    /// Wake all waiters on this shard, called after a key is inserted
+    #[inline]
+    pub(crate) fn notify(&self) {
+        if self.waiters.load(Ordering::SeqCst) > 0 {
+            self.epoch.fetch_add(1, Ordering::SeqCst);
+            let wakers = std::mem::take(&mut *self.wakers.lock());
+            self.cond.notify_all();
+            wakers.into_iter().for_each(|(_, waker)| waker.wake());
+        }
+    }
+}
+
+impl<K, V, S: BuildHasher, const N: usize, P> ConcurrentHashMap<K, V, S, N, P> {
+    /// Blocks until `key` is present, then returns a guarded reference to its value.
+    ///
+    /// The caller is parked on its shard's condition variable rather than polling, and woken
+    /// whenever a key is inserted into that shard.
+    ///
+    /// **Locks** - Acquires a read lock on one of `N` shards each time the caller wakes, and
+    /// holds it for the returned guard.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use sharded::ConcurrentHashMap;
+    /// use std::sync::Arc;
+    ///
+    /// let map = Arc::new(ConcurrentHashMap::new());
+    ///
+    /// let writer = map.clone();
+    /// std::thread::spawn(move || writer.insert("ready", 1));
+    ///
+    /// assert_eq!(*map.wait_for(&"ready"), 1);
+    /// ```
+    pub fn wait_for<Q>(&self, key: &Q) -> ReadGuard<'_, V>
+    where
+        Q: Hash + Equivalent<K> + ?Sized,
+        P: ShardSelector<Q>,
+    {
+        loop {
+            if let Some(value) = self.wait_until(key, None) {
+                return value;
+            }
+        }
+    }
+
+    /// Like [`wait_for`](Self::wait_for), but gives up and returns `None` once `timeout` has
+    /// elapsed without the key appearing.
+    ///
+    /// **Locks** - Acquires a read lock on one of `N` shards each time the caller wakes, and
+    /// holds it for the returned guard.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use sharded::ConcurrentHashMap;
+    /// use std::time::Duration;
+    ///
+    /// let map: ConcurrentHashMap<&str, i32> = ConcurrentHashMap::new();
+    /// assert!(map.wait_for_timeout(&"never", Duration::from_millis(10)).is_none());
+    /// ```
+    pub fn wait_for_timeout<Q>(&self, key: &Q, timeout: Duration) -> Option<ReadGuard<'_, V>>
+    where
+        Q: Hash + Equivalent<K> + ?Sized,
+        P: ShardSelector<Q>,
+    {
+        self.wait_until(key, Some(Instant::now() + timeout))
+    }
+
+    fn wait_until<Q>(&self, key: &Q, deadline: Option<Instant>) -> Option<ReadGuard<'_, V>>
+    where
+        Q: Hash + Equivalent<K> + ?Sized,
+        P: ShardSelector<Q>,
+    {
+        let hash = make_hash::<Q, _>(&self.hash_builder, key);
+        let i = self.shard_for(hash, key);
+        let notifier = &self.notifiers[i];
+
+        notifier.waiters.fetch_add(1, Ordering::SeqCst);
+
+        let value = loop {
+            let epoch = notifier.epoch.load(Ordering::SeqCst);
+
+            if let Some(value) = self.shards[i].read().try_map(|shard| shard.get(hash, key)) {
+                break Some(value);
+            }
+
+            let mut parked = notifier.wakers.lock();
+            if notifier.epoch.load(Ordering::SeqCst) != epoch {
+                continue;
+            }
+
+            match deadline {
+                Some(deadline) => {
+                    if notifier.cond.wait_until(&mut parked, deadline).timed_out() {
+                        drop(parked);
+                        break self.shards[i].read().try_map(|shard| shard.get(hash, key));
+                    }
+                }
+                None => notifier.cond.wait(&mut parked),
+            }
+        };
+
+        notifier.waiters.fetch_sub(1, Ordering::SeqCst);
+        value
+    }
+
+    /// Like [`wait_for`](Self::wait_for), but returns a future that resolves once `key` is
+    /// present instead of blocking the thread. It is woken by the same inserts and works with
+    /// any executor.
+    ///
+    /// Only available with the `async` feature. The resulting guard holds a shard lock, so
+    /// drop it before the next `.await`.
+    ///
+    /// **Locks** - Acquires a read lock on one of `N` shards each time the future is polled,
+    /// and holds it for the returned guard.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use sharded::ConcurrentHashMap;
+    /// use std::future::Future;
+    /// use std::pin::pin;
+    /// use std::task::{Context, Poll, Waker};
+    ///
+    /// let map = ConcurrentHashMap::new();
+    /// let mut ready = pin!(map.wait_for_async(&"ready"));
+    /// let mut cx = Context::from_waker(Waker::noop());
+    /// assert!(ready.as_mut().poll(&mut cx).is_pending());
+    ///
+    /// map.insert("ready", 1);
+    /// match ready.poll(&mut cx) {
+    ///     Poll::Ready(value) => assert_eq!(*value, 1),
+    ///     Poll::Pending => unreachable!(),
+    /// };
+    /// ```
+    #[cfg(feature = "async")]
+    pub fn wait_for_async<'a, Q>(&'a self, key: &'a Q) -> WaitFor<'a, K, V, S, N, P, Q>
+    where
+        Q: Hash + Equivalent<K> + ?Sized,
+        P: ShardSelector<Q>,
+    {
+        let hash = make_hash::<Q, _>(&self.hash_builder, key);
+        let shard = self.shard_for(hash, key);
+        self.notifiers[shard].waiters.fetch_add(1, Ordering::SeqCst);
+
+        WaitFor {
+            map: self,
+            key,
+            hash,
+            shard,
+            registration: None,
+        }
+    }
+}
+
+/// A future that resolves once a key is present, see
+/// [`ConcurrentHashMap::wait_for_async`].
+#[cfg(feature = "async")]
+#[must_use = "futures do nothing unless polled"]
+pub struct WaitFor<'a, K, V, S, const N: usize, P, Q: ?Sized> {
+    map: &'a ConcurrentHashMap<K, V, S, N, P>,
+    key: &'a Q,
+    hash: u64,
+    shard: usize,
+    // the id of this future's waker in the shard's notifier, once it has been pending
+    registration: Option<usize>,
+}
+
+#[cfg(feature = "async")]
+impl<'a, K, V, S, const N: usize, P, Q> Future for WaitFor<'a, K, V, S, N, P, Q>
+where
+    Q: Equivalent<K> + ?Sized,
+{
+    type Output = ReadGuard<'a, V>;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        let this = self.get_mut();
+        let (map, key, hash) = (this.map, this.key, this.hash);
+        let notifier = &map.notifiers[this.shard];
+
+        loop {
+            let epoch = notifier.epoch.load(Ordering::SeqCst);
+
+            if let Some(value) = map.shards[this.shard]
+                .read()
+                .try_map(|shard| shard.get(hash, key))
+            {
+                this.unregister();
+                return Poll::Ready(value);
+            }
+
+            let mut wakers = notifier.wakers.lock();
+            if notifier.epoch.load(Ordering::SeqCst) != epoch {
+                continue;
+            }
+            let id = *this
+                .registration
+                .get_or_insert_with(|| notifier.registrations.fetch_add(1, Ordering::Relaxed));
+            match wakers.iter_mut().find(|(registered, _)| *registered == id) {
+                Some((_, waker)) => waker.clone_from(cx.waker()),
+                // first poll, or a notify took the previous waker
+                None => wakers.push((id, cx.waker().clone())),
+            }
+            return Poll::Pending;
+        }
+    }
+}
+
+#[cfg(feature = "async")]
+impl<K, V, S, const N: usize, P, Q: ?Sized> WaitFor<'_, K, V, S, N, P, Q> {
+    /// Removes this future's waker from the notifier, if it is still registered
+    fn unregister(&mut self) {
+        if let Some(id) = self.registration.take() {
+            let mut wakers = self.map.notifiers[self.shard].wakers.lock();
+            if let Some(i) = wakers.iter().position(|(registered, _)| *registered == id) {
+                wakers.swap_remove(i);
+            }
+        }
+    }
+}
+
+#[cfg(feature = "async")]
+impl<K, V, S, const N: usize, P, Q: ?Sized> Drop for WaitFor<'_, K, V, S, N, P, Q> {
+    fn drop(&mut self) {
+        self.unregister();
+        self.map.notifiers[self.shard]
+            .waiters
+            .fetch_sub(1, Ordering::SeqCst);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::ConcurrentHashMap;
+    use std::collections::hash_map::RandomState;
+    use std::sync::Arc;
+    use std::time::Duration;
+
+    #[test]
+    fn test_waiters_wake_on_every_insert_path() {
+        let map: Arc<ConcurrentHashMap<u32, u32, RandomState, 2>> =
+            Arc::new(ConcurrentHashMap::default());
+
+        let waiters: Vec<_> = (0..6)
+            .map(|key| {
+                let map = map.clone();
+                std::thread::spawn(move || *map.wait_for(&key))
+            })
+            .collect();
+
+        std::thread::sleep(Duration::from_millis(50));
+        map.insert(0, 0);
+        assert!(map.try_insert(1, 1).is_ok());
+        map.insert_batch([(2, 2), (3, 3)]);
+        map.transact(&[&4], |tx| tx.insert(4, 4));
+        assert!(map.get_or_try_insert_with(5, || Ok::<_, ()>(5)).is_ok());
+
+        for (key, waiter) in waiters.into_iter().enumerate() {
+            assert_eq!(waiter.join().unwrap(), key as u32);
+        }
+
+        assert!(map
+            .wait_for_timeout(&99, Duration::from_millis(10))
+            .is_none());
+    }
+
+    #[cfg(feature = "async")]
+    #[test]
+    fn test_wait_for_async_is_woken_by_insert() {
+        use std::future::Future;
+        use std::task::{Context, Poll, Wake, Waker};
+        use std::thread::Thread;
+
+        struct Unpark(Thread);
+
+        impl Wake for Unpark {
+            fn wake(self: Arc<Self>) {
+                self.0.unpark();
+            }
+        }
+
+        let map: Arc<ConcurrentHashMap<u32, u32, RandomState, 2>> =
+            Arc::new(ConcurrentHashMap::default());
+
+        let writer = {
+            let map = map.clone();
+            std::thread::spawn(move || {
+                std::thread::sleep(Duration::from_millis(50));
+                map.insert(1, 10);
+            })
+        };
+
+        let waker = Waker::from(Arc::new(Unpark(std::thread::current())));
+        let mut cx = Context::from_waker(&waker);
+        let mut wait = std::pin::pin!(map.wait_for_async(&1));
+        let value = loop {
+            match wait.as_mut().poll(&mut cx) {
+                Poll::Ready(value) => break *value,
+                Poll::Pending => std::thread::park(),
+            }
+        };
+        assert_eq!(value, 10);
+        writer.join().unwrap();
+
+        // dropping a pending future unregisters it
+        let mut pending = Box::pin(map.wait_for_async(&2));
+        assert!(pending.as_mut().poll(&mut cx).is_pending());
+        drop(pending);
+        assert!(map.notifiers.iter().all(|n| n.wakers.lock().is_empty()));
+    }
+
+    #[cfg(feature = "async")]
+    #[test]
+    fn test_futures_sharing_a_task_keep_their_own_wakers() {
+        use std::future::Future;
+        use std::sync::atomic::{AtomicUsize, Ordering};
+        use std::task::{Context, Wake, Waker};
+
+        #[derive(Default)]
+        struct Count(AtomicUsize);
+
+        impl Wake for Count {
+            fn wake(self: Arc<Self>) {
+                self.0.fetch_add(1, Ordering::SeqCst);
+            }
+        }
+
+        let map: ConcurrentHashMap<u32, u32, RandomState, 1> = ConcurrentHashMap::default();
+        let count = Arc::new(Count::default());
+        let waker = Waker::from(count.clone());
+        let mut cx = Context::from_waker(&waker);
+
+        // one task awaiting two keys of the same shard, as with `join!`
+        let mut first = Box::pin(map.wait_for_async(&1));
+        let mut second = Box::pin(map.wait_for_async(&2));
+        assert!(first.as_mut().poll(&mut cx).is_pending());
+        assert!(second.as_mut().poll(&mut cx).is_pending());
+        assert_eq!(map.notifiers[0].wakers.lock().len(), 2);
+
+        map.insert(1, 1);
+        assert_eq!(count.0.load(Ordering::SeqCst), 2);
+        assert!(first.as_mut().poll(&mut cx).is_ready());
+        assert!(second.as_mut().poll(&mut cx).is_pending());
+
+        // completing and dropping the first future leaves the second registered
+        drop(first);
+        assert_eq!(map.notifiers[0].wakers.lock().len(), 1);
+        map.insert(2, 2);
+        assert_eq!(count.0.load(Ordering::SeqCst), 3);
+        assert!(second.as_mut().poll(&mut cx).is_ready());
+    }
+}